@@ -0,0 +1,349 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+    sync::{LazyLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table::{
+        AddressLookupTableAccount,
+        instruction::{create_lookup_table, extend_lookup_table},
+        state::AddressLookupTable,
+    },
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+
+use crate::{
+    core::{builder::TxBuilder, confirm::send_and_confirm_builder},
+    utils::jjj::import_env_var_with_default,
+};
+
+// addresses that fit in one extend transaction
+const EXTEND_CHUNK_SIZE: usize = 20;
+
+static LOOKUP_TABLES: LazyLock<LookupTableManager> = LazyLock::new(|| {
+    LookupTableManager::new(PathBuf::from(import_env_var_with_default(
+        "ALT_STORE_PATH",
+        "./config/alt.json".to_string(),
+    )))
+});
+
+/// table name -> table address, as persisted on disk
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct LookupTableStore {
+    tables: BTreeMap<String, String>,
+}
+
+struct CachedTables {
+    accounts: Vec<AddressLookupTableAccount>,
+    loaded_at: Instant,
+}
+
+/// Keeps the lookup tables holding the static program and market accounts
+/// we trade against. Table addresses are persisted to `path`, table contents
+/// are fetched from chain and cached in memory for ALT_REFRESH_SECS, or until
+/// a swap needs an address none of the cached tables has.
+pub struct LookupTableManager {
+    path: PathBuf,
+    store: RwLock<Option<LookupTableStore>>,
+    accounts: RwLock<Option<CachedTables>>,
+    refresh_interval: Duration,
+    /// minimum age of the cache before a miss reloads it
+    miss_refresh_interval: Duration,
+}
+
+impl LookupTableManager {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            store: RwLock::new(None),
+            accounts: RwLock::new(None),
+            refresh_interval: Duration::from_secs(import_env_var_with_default(
+                "ALT_REFRESH_SECS",
+                300,
+            )),
+            miss_refresh_interval: Duration::from_secs(import_env_var_with_default(
+                "ALT_MISS_REFRESH_SECS",
+                10,
+            )),
+        }
+    }
+
+    async fn load_store(&self) -> Result<LookupTableStore> {
+        if let Some(store) = self.store.read().unwrap().as_ref() {
+            return Ok(store.clone());
+        }
+        let store = match tokio::fs::read(&self.path).await {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => LookupTableStore::default(),
+            Err(err) => return Err(err.into()),
+        };
+        *self.store.write().unwrap() = Some(store.clone());
+        Ok(store)
+    }
+
+    async fn save_store(&self, store: LookupTableStore) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&self.path, serde_json::to_vec_pretty(&store)?).await?;
+        *self.store.write().unwrap() = Some(store);
+        Ok(())
+    }
+
+    /// all stored tables, fetched again from chain once the cache is older
+    /// than ALT_REFRESH_SECS
+    pub async fn load(&self, client: &RpcClient) -> Result<Vec<AddressLookupTableAccount>> {
+        if let Some(cached) = self.accounts.read().unwrap().as_ref() {
+            if cached.loaded_at.elapsed() < self.refresh_interval {
+                return Ok(cached.accounts.clone());
+            }
+        }
+        let store = self.load_store().await?;
+        let mut accounts = vec![];
+        for (name, key) in store.tables.iter() {
+            let key: Pubkey = key.parse()?;
+            match get_lookup_table(client, &key).await {
+                Ok(account) => accounts.push(account),
+                Err(err) => warn!("lookup table {}({}) not loaded: {}", name, key, err),
+            }
+        }
+        *self.accounts.write().unwrap() = Some(CachedTables {
+            accounts: accounts.clone(),
+            loaded_at: Instant::now(),
+        });
+        Ok(accounts)
+    }
+
+    /// drop the cached table contents, e.g. after a table was deactivated or
+    /// extended elsewhere, the next load fetches them again
+    pub fn invalidate(&self) {
+        *self.accounts.write().unwrap() = None;
+    }
+
+    fn cache_age(&self) -> Option<Duration> {
+        self.accounts
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|cached| cached.loaded_at.elapsed())
+    }
+
+    /// the cached tables that cover at least one of `addresses`
+    pub async fn tables_for(
+        &self,
+        client: &RpcClient,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<AddressLookupTableAccount>> {
+        if addresses.is_empty() {
+            return Ok(vec![]);
+        }
+        let tables = self.load(client).await?;
+        let (selected, missed) = select_tables(&tables, addresses);
+        if !missed
+            || self
+                .cache_age()
+                .is_some_and(|age| age < self.miss_refresh_interval)
+        {
+            return Ok(selected);
+        }
+        // the address may have been added to a table since the last load
+        self.invalidate();
+        let tables = self.load(client).await?;
+        Ok(select_tables(&tables, addresses).0)
+    }
+
+    /// make sure the table stored under `name` holds every one of `addresses`,
+    /// creating the table first if there is none yet
+    pub async fn ensure_table(
+        &self,
+        client: &RpcClient,
+        authority: &Keypair,
+        name: &str,
+        addresses: &[Pubkey],
+    ) -> Result<AddressLookupTableAccount> {
+        let mut store = self.load_store().await?;
+        let table = match store.tables.get(name) {
+            Some(key) => key.parse::<Pubkey>()?,
+            None => {
+                let table = create_table(client, authority).await?;
+                store.tables.insert(name.to_string(), table.to_string());
+                self.save_store(store).await?;
+                info!("lookup table {} created: {}", name, table);
+                table
+            }
+        };
+
+        let existing: HashSet<Pubkey> = match get_lookup_table(client, &table).await {
+            Ok(account) => account.addresses.into_iter().collect(),
+            // a table created in this call may not be visible yet
+            Err(_) => HashSet::new(),
+        };
+        let missing = missing_addresses(&existing, addresses);
+        for chunk in missing.chunks(EXTEND_CHUNK_SIZE) {
+            extend_table(authority, &table, chunk.to_vec()).await?;
+        }
+        if !missing.is_empty() {
+            info!(
+                "lookup table {}({}) extended with {} addresses",
+                name,
+                table,
+                missing.len()
+            );
+        }
+
+        // tables can only be used from the slot after they were extended, so
+        // refresh the cache now and let the next swap pick the new entries up
+        let account = get_lookup_table(client, &table).await?;
+        if let Some(cached) = self.accounts.write().unwrap().as_mut() {
+            cached.accounts.retain(|a| a.key != table);
+            cached.accounts.push(account.clone());
+        }
+        Ok(account)
+    }
+}
+
+/// the tables that cover at least one of `addresses`, and whether some of
+/// `addresses` is in none of `tables`
+fn select_tables(
+    tables: &[AddressLookupTableAccount],
+    addresses: &[Pubkey],
+) -> (Vec<AddressLookupTableAccount>, bool) {
+    let selected: Vec<AddressLookupTableAccount> = tables
+        .iter()
+        .filter(|table| table.addresses.iter().any(|a| addresses.contains(a)))
+        .cloned()
+        .collect();
+    let missed = addresses.iter().any(|address| {
+        !selected
+            .iter()
+            .any(|table| table.addresses.contains(address))
+    });
+    (selected, missed)
+}
+
+/// `addresses` not in `existing` yet, each once and in order
+fn missing_addresses(existing: &HashSet<Pubkey>, addresses: &[Pubkey]) -> Vec<Pubkey> {
+    let mut missing = vec![];
+    for address in addresses {
+        if !existing.contains(address) && !missing.contains(address) {
+            missing.push(*address);
+        }
+    }
+    missing
+}
+
+pub async fn get_lookup_table(
+    client: &RpcClient,
+    table: &Pubkey,
+) -> Result<AddressLookupTableAccount> {
+    let data = client.get_account_data(table)?;
+    let lookup_table = AddressLookupTable::deserialize(&data)
+        .map_err(|e| anyhow!("invalid lookup table {}: {}", table, e))?;
+    Ok(AddressLookupTableAccount {
+        key: *table,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+async fn create_table(client: &RpcClient, authority: &Keypair) -> Result<Pubkey> {
+    // the derivation slot has to be a recent one that is already finalized
+    let recent_slot = client.get_slot_with_commitment(CommitmentConfig::finalized())?;
+    let (instruction, table) =
+        create_lookup_table(authority.pubkey(), authority.pubkey(), recent_slot);
+    send_and_confirm(TxBuilder::new(authority).instruction(instruction)).await?;
+    Ok(table)
+}
+
+async fn extend_table(authority: &Keypair, table: &Pubkey, addresses: Vec<Pubkey>) -> Result<()> {
+    let instruction = extend_lookup_table(
+        *table,
+        authority.pubkey(),
+        Some(authority.pubkey()),
+        addresses,
+    );
+    send_and_confirm(TxBuilder::new(authority).instruction(instruction)).await
+}
+
+async fn send_and_confirm(builder: TxBuilder<'_>) -> Result<()> {
+    let signature = send_and_confirm_builder(&builder).await?;
+    info!("lookup table tx: {}", signature);
+    Ok(())
+}
+
+/// process wide manager, stored at ALT_STORE_PATH
+pub fn get_lookup_table_manager() -> &'static LookupTableManager {
+    &LOOKUP_TABLES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(addresses: &[Pubkey]) -> AddressLookupTableAccount {
+        AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: addresses.to_vec(),
+        }
+    }
+
+    #[test]
+    fn missing_addresses_are_deduplicated() {
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let existing = HashSet::from([a]);
+        assert_eq!(
+            missing_addresses(&existing, &[a, b, c, b, a, c]),
+            vec![b, c]
+        );
+        assert!(missing_addresses(&HashSet::from([a, b]), &[b, a]).is_empty());
+    }
+
+    #[test]
+    fn only_covering_tables_are_selected() {
+        let [a, b, c, d] = [(); 4].map(|_| Pubkey::new_unique());
+        let tables = vec![table(&[a, b]), table(&[c]), table(&[d])];
+        let (selected, missed) = select_tables(&tables, &[a, c]);
+        assert_eq!(selected, vec![tables[0].clone(), tables[1].clone()]);
+        assert!(!missed);
+
+        let (selected, missed) = select_tables(&tables, &[b, Pubkey::new_unique()]);
+        assert_eq!(selected, vec![tables[0].clone()]);
+        assert!(missed);
+        assert_eq!(select_tables(&[], &[a]), (vec![], true));
+    }
+
+    #[test]
+    fn cache_expires_and_invalidates() {
+        let client = RpcClient::new("http://127.0.0.1:1".to_string());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut manager =
+            LookupTableManager::new(std::env::temp_dir().join("alt-test-missing/alt.json"));
+        let cached = table(&[Pubkey::new_unique()]);
+        let fill = |manager: &LookupTableManager| {
+            *manager.accounts.write().unwrap() = Some(CachedTables {
+                accounts: vec![cached.clone()],
+                loaded_at: Instant::now(),
+            });
+        };
+
+        // served from memory without touching the rpc
+        fill(&manager);
+        let tables = runtime.block_on(manager.load(&client)).unwrap();
+        assert_eq!(tables, vec![cached.clone()]);
+
+        // reloaded from the empty store once invalidated or expired
+        manager.invalidate();
+        assert!(runtime.block_on(manager.load(&client)).unwrap().is_empty());
+        manager.refresh_interval = Duration::ZERO;
+        fill(&manager);
+        assert!(runtime.block_on(manager.load(&client)).unwrap().is_empty());
+    }
+}
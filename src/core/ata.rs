@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::info;
+use solana_program::system_instruction;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::extension::{BaseStateWithExtensions, transfer_fee::TransferFeeAmount};
+use spl_token_client::token::TokenError;
+
+use crate::core::token;
+
+/// Instructions that have to wrap a swap so it leaves no orphaned accounts
+/// or stranded SOL behind: `setup` runs before the swap, `cleanup` after it.
+#[derive(Clone, Debug, Default)]
+pub struct SwapAccounts {
+    pub setup: Vec<Instruction>,
+    pub cleanup: Vec<Instruction>,
+}
+
+impl SwapAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// create the ata for `mint` unless it already exists
+    pub fn create_ata(&mut self, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+        self.setup.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            mint,
+            token_program,
+        ));
+        get_associated_token_address_with_program_id(owner, mint, token_program)
+    }
+
+    /// create the wsol ata and fund it with `lamports`, it is closed again
+    /// after the swap so whatever was not spent goes back to the owner
+    pub fn wrap_sol(&mut self, owner: &Pubkey, lamports: u64) -> Pubkey {
+        let wsol_ata = self.create_ata(owner, &spl_token::native_mint::ID, &spl_token::ID);
+        self.setup
+            .push(system_instruction::transfer(owner, &wsol_ata, lamports));
+        self.setup
+            .push(spl_token::instruction::sync_native(&spl_token::ID, &wsol_ata).unwrap());
+        self.close_wsol(owner);
+        wsol_ata
+    }
+
+    /// receive wsol from the swap and unwrap it straight away
+    pub fn receive_sol(&mut self, owner: &Pubkey) -> Pubkey {
+        let wsol_ata = self.create_ata(owner, &spl_token::native_mint::ID, &spl_token::ID);
+        self.close_wsol(owner);
+        wsol_ata
+    }
+
+    fn close_wsol(&mut self, owner: &Pubkey) {
+        let wsol_ata = get_associated_token_address_with_program_id(
+            owner,
+            &spl_token::native_mint::ID,
+            &spl_token::ID,
+        );
+        let close = close_account(&spl_token::ID, &wsol_ata, owner);
+        if !self.cleanup.contains(&close) {
+            self.cleanup.push(close);
+        }
+    }
+
+    /// close the token ata to get the rent back
+    pub fn close_ata(&mut self, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) {
+        let ata = get_associated_token_address_with_program_id(owner, mint, token_program);
+        self.cleanup.push(close_account(token_program, &ata, owner));
+    }
+
+    /// setup, swap and cleanup in execution order
+    pub fn wrap(self, swap_instructions: Vec<Instruction>) -> Vec<Instruction> {
+        let mut instructions = self.setup;
+        instructions.extend(swap_instructions);
+        instructions.extend(self.cleanup);
+        instructions
+    }
+}
+
+fn close_account(token_program: &Pubkey, account: &Pubkey, owner: &Pubkey) -> Instruction {
+    spl_token_2022::instruction::close_account(token_program, account, owner, owner, &[owner])
+        .unwrap()
+}
+
+/// whether selling `amount_in` empties the owner's token account so it can be
+/// closed afterwards. token-2022 accounts still holding withheld transfer fees
+/// can not be closed and are left alone.
+pub async fn is_full_sell(
+    client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    keypair: Arc<Keypair>,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    amount_in: u64,
+) -> Result<bool> {
+    let owner = keypair.pubkey();
+    let ata = get_associated_token_address_with_program_id(&owner, mint, token_program);
+    let account = match token::get_account_info(client, keypair, mint, &ata).await {
+        Ok(account) => account,
+        Err(TokenError::AccountNotFound) => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    if account.base.amount > amount_in {
+        return Ok(false);
+    }
+    if let Ok(fee_amount) = account.get_extension::<TransferFeeAmount>() {
+        let withheld: u64 = fee_amount.withheld_amount.into();
+        if withheld > 0 {
+            info!(
+                "token account {} holds {} withheld transfer fee, keep it open",
+                ata, withheld
+            );
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
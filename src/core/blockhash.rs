@@ -0,0 +1,279 @@
+use std::{
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures_util::StreamExt;
+use log::{info, warn};
+use solana_client::nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient};
+use solana_sdk::{commitment_config::CommitmentConfig, hash::Hash};
+use tokio::time::{Instant, sleep};
+
+use crate::utils::jjj::{import_env_var, import_env_var_with_default, import_env_var_with_option};
+
+/// a blockhash is valid for 150 blocks after the one it was taken from
+pub const MAX_PROCESSING_AGE: u64 = 150;
+const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+static BLOCKHASH_CACHE: LazyLock<Option<Arc<BlockhashCache>>> = LazyLock::new(|| {
+    // the refresh task needs a runtime, callers outside of one fetch themselves
+    if tokio::runtime::Handle::try_current().is_err() {
+        warn!("blockhash cache not started outside a runtime, fetching per transaction");
+        return None;
+    }
+    let rpc_client = Arc::new(RpcClient::new_with_commitment(
+        import_env_var("RPC_HTTPS"),
+        CommitmentConfig::confirmed(),
+    ));
+    let refresh = match import_env_var_with_option("BLOCKHASH_REFRESH_SLOTS")
+        .and_then(|slots| slots.into_string().ok()?.parse().ok())
+    {
+        Some(slots) => BlockhashRefresh::Slots(slots),
+        None => BlockhashRefresh::Timer(Duration::from_millis(import_env_var_with_default(
+            "BLOCKHASH_REFRESH_MS",
+            1000,
+        ))),
+    };
+    Some(BlockhashCache::spawn(
+        rpc_client,
+        refresh,
+        import_env_var_with_default("BLOCKHASH_MIN_BLOCKS_LEFT", 30),
+    ))
+});
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CachedBlockhash {
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
+    pub fetched_at: Instant,
+}
+
+impl CachedBlockhash {
+    /// blocks left before expiry at `block_height`
+    pub fn blocks_left(&self, block_height: u64) -> u64 {
+        self.last_valid_block_height.saturating_sub(block_height)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlockhashRefresh {
+    Timer(Duration),
+    /// every n slots, following slotSubscribe on RPC_WSS
+    Slots(u64),
+}
+
+/// Latest blockhash kept fresh in the background so signing doesn't wait
+/// on a `getLatestBlockhash` round trip. Expiry is judged against the block
+/// height from the last refresh, moved on by every slot seen since.
+pub struct BlockhashCache {
+    rpc_client: Arc<RpcClient>,
+    min_blocks_left: u64,
+    latest: RwLock<Option<CachedBlockhash>>,
+    /// skipped slots make no block, so counting slots overestimates the
+    /// height and errs on the side of expiry
+    block_height: AtomicU64,
+    slot: AtomicU64,
+}
+
+impl BlockhashCache {
+    fn new(rpc_client: Arc<RpcClient>, min_blocks_left: u64) -> Self {
+        Self {
+            rpc_client,
+            min_blocks_left,
+            latest: RwLock::new(None),
+            block_height: AtomicU64::new(0),
+            slot: AtomicU64::new(0),
+        }
+    }
+
+    pub fn spawn(
+        rpc_client: Arc<RpcClient>,
+        refresh: BlockhashRefresh,
+        min_blocks_left: u64,
+    ) -> Arc<Self> {
+        let cache = Arc::new(Self::new(rpc_client, min_blocks_left));
+        let task = cache.clone();
+        tokio::spawn(async move {
+            match refresh {
+                BlockhashRefresh::Timer(interval) => task.run_timer(interval).await,
+                BlockhashRefresh::Slots(slots) => loop {
+                    if let Err(err) = task.run_slots(slots).await {
+                        warn!("blockhash slot subscription failed: {}", err);
+                    }
+                    // keep the cache alive on the timer until the socket is back
+                    task.refresh().await.ok();
+                    sleep(SLOT_DURATION * slots.max(1) as u32).await;
+                },
+            }
+        });
+        cache
+    }
+
+    async fn run_timer(&self, interval: Duration) {
+        loop {
+            if let Err(err) = self.refresh().await {
+                warn!("blockhash refresh failed: {}", err);
+            }
+            sleep(interval).await;
+        }
+    }
+
+    async fn run_slots(&self, slots: u64) -> Result<()> {
+        let wss_url = import_env_var("RPC_WSS");
+        let pubsub_client = PubsubClient::new(&wss_url).await?;
+        let (mut stream, _unsubscribe) = pubsub_client.slot_subscribe().await?;
+        info!("blockhash cache following slots on {}", wss_url);
+        while let Some(slot_info) = stream.next().await {
+            self.observe_slot(slot_info.slot);
+            if slot_info.slot % slots.max(1) == 0 {
+                if let Err(err) = self.refresh().await {
+                    warn!("blockhash refresh failed: {}", err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn refresh(&self) -> Result<CachedBlockhash> {
+        let commitment = CommitmentConfig::confirmed();
+        let (latest, block_height) = tokio::join!(
+            self.rpc_client
+                .get_latest_blockhash_with_commitment(commitment),
+            self.rpc_client.get_block_height_with_commitment(commitment)
+        );
+        let (blockhash, last_valid_block_height) = latest?;
+        self.observe_block_height(block_height?);
+        let cached = CachedBlockhash {
+            blockhash,
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        };
+        *self.latest.write().unwrap() = Some(cached);
+        Ok(cached)
+    }
+
+    fn observe_block_height(&self, block_height: u64) {
+        self.block_height.fetch_max(block_height, Ordering::Relaxed);
+    }
+
+    // every slot past the last one seen may have made a block
+    fn observe_slot(&self, slot: u64) {
+        let previous = self.slot.fetch_max(slot, Ordering::Relaxed);
+        if previous != 0 && slot > previous {
+            self.block_height
+                .fetch_add(slot - previous, Ordering::Relaxed);
+        }
+    }
+
+    /// best known current block height
+    pub fn block_height(&self) -> u64 {
+        self.block_height.load(Ordering::Relaxed)
+    }
+
+    /// latest blockhash, including one that is close to expiry
+    pub fn latest(&self) -> Option<CachedBlockhash> {
+        *self.latest.read().unwrap()
+    }
+
+    /// latest blockhash, None when there is none yet or it is too close to
+    /// expiry for a transaction to still land on it
+    pub fn get(&self) -> Option<CachedBlockhash> {
+        self.latest().filter(|cached| !self.is_near_expiry(cached))
+    }
+
+    pub fn is_near_expiry(&self, cached: &CachedBlockhash) -> bool {
+        cached.blocks_left(self.block_height()) < self.min_blocks_left
+    }
+}
+
+/// process wide cache, see BLOCKHASH_REFRESH_MS, BLOCKHASH_REFRESH_SLOTS and
+/// BLOCKHASH_MIN_BLOCKS_LEFT
+pub fn get_blockhash_cache() -> Option<Arc<BlockhashCache>> {
+    BLOCKHASH_CACHE.clone()
+}
+
+/// cached blockhash when there is a fresh one, an rpc round trip otherwise
+pub fn get_latest_blockhash(client: &solana_client::rpc_client::RpcClient) -> Result<(Hash, u64)> {
+    if let Some(cached) = get_blockhash_cache().and_then(|cache| cache.get()) {
+        return Ok((cached.blockhash, cached.last_valid_block_height));
+    }
+    Ok(client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> BlockhashCache {
+        let rpc_client = Arc::new(RpcClient::new("http://127.0.0.1:1".to_string()));
+        BlockhashCache::new(rpc_client, 30)
+    }
+
+    fn cached(last_valid_block_height: u64) -> CachedBlockhash {
+        CachedBlockhash {
+            blockhash: Hash::new_unique(),
+            last_valid_block_height,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn blocks_left_counts_to_last_valid_height() {
+        let cached = cached(1_150);
+        assert_eq!(cached.blocks_left(1_000), 150);
+        assert_eq!(cached.blocks_left(1_149), 1);
+        assert_eq!(cached.blocks_left(1_200), 0);
+    }
+
+    #[tokio::test]
+    async fn near_expiry_follows_block_height() {
+        let cache = cache();
+        let cached = cached(1_000 + MAX_PROCESSING_AGE);
+        cache.observe_block_height(1_000);
+        assert!(!cache.is_near_expiry(&cached));
+        cache.observe_block_height(1_000 + MAX_PROCESSING_AGE - 30);
+        assert!(!cache.is_near_expiry(&cached));
+        cache.observe_block_height(1_000 + MAX_PROCESSING_AGE - 29);
+        assert!(cache.is_near_expiry(&cached));
+        // an older height from a lagging node doesn't move it back
+        cache.observe_block_height(1_000);
+        assert!(cache.is_near_expiry(&cached));
+    }
+
+    #[tokio::test]
+    async fn slots_move_the_height_on() {
+        let cache = cache();
+        cache.observe_block_height(1_000);
+        // the first slot only sets the reference point
+        cache.observe_slot(5_000);
+        assert_eq!(cache.block_height(), 1_000);
+        cache.observe_slot(5_003);
+        assert_eq!(cache.block_height(), 1_003);
+        // out of order notifications are ignored
+        cache.observe_slot(5_002);
+        assert_eq!(cache.block_height(), 1_003);
+        // a refresh can only correct it upwards
+        cache.observe_block_height(1_001);
+        assert_eq!(cache.block_height(), 1_003);
+    }
+
+    #[tokio::test]
+    async fn get_skips_missing_and_expiring_blockhashes() {
+        let cache = cache();
+        assert_eq!(cache.get(), None);
+
+        let fresh = cached(1_000 + MAX_PROCESSING_AGE);
+        *cache.latest.write().unwrap() = Some(fresh);
+        cache.observe_block_height(1_000);
+        assert_eq!(cache.get(), Some(fresh));
+
+        cache.observe_block_height(1_000 + MAX_PROCESSING_AGE);
+        assert_eq!(cache.get(), None);
+        // still there for callers that accept an expiring one
+        assert_eq!(cache.latest(), Some(fresh));
+    }
+}
@@ -0,0 +1,538 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures_util::future::join_all;
+use log::{info, warn};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, pubkey::Pubkey,
+    signature::Signature, transaction::TransactionError, transaction::VersionedTransaction,
+};
+use tokio::time::{Instant, sleep};
+
+use crate::{
+    core::tx::SendChannel,
+    service::{
+        block_engine::{BlockEngine, get_block_engines},
+        jito::{self, JitoMode, JitoRequest, get_jito_sdk},
+        nextblock::NextBlockClient,
+    },
+    utils::jjj::{create_nonblocking_rpc_client, import_env_var_with_default},
+};
+
+static LANDED_VIA: LazyLock<Mutex<HashMap<String, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// What one channel answered when the transaction was handed to it.
+#[derive(Debug)]
+pub struct Submission {
+    pub channel: SendChannel,
+    /// signature, or bundle id for jito bundles
+    pub result: Result<String>,
+    pub elapsed: Duration,
+    /// fastest regional engine that accepted it, when JITO_BLOCK_ENGINES is set
+    pub engine: Option<BlockEngine>,
+}
+
+#[derive(Debug)]
+pub struct BroadcastResult {
+    pub signature: Signature,
+    pub slot: u64,
+    /// set when the transaction landed but failed
+    pub err: Option<TransactionError>,
+    /// channel that provably landed it, only known for jito bundles
+    pub landed_via: Option<SendChannel>,
+    pub submissions: Vec<Submission>,
+}
+
+/// Sends one signed transaction through several channels at once and waits
+/// for the first confirmation. Every channel gets the very same bytes, so the
+/// transaction can only land once, but every tip inside it pays out when it
+/// does. Only the first paid provider in the list is kept and tipped, unless
+/// `tip_all` opts into paying each of them.
+pub struct Broadcaster {
+    channels: Vec<SendChannel>,
+    uuid_string: Option<String>,
+    timeout: Duration,
+}
+
+impl Broadcaster {
+    pub fn new(channels: Vec<SendChannel>, uuid_string: Option<String>, tip_all: bool) -> Self {
+        let mut tipped = None;
+        let channels = channels
+            .into_iter()
+            .filter(|channel| *channel != SendChannel::Broadcast)
+            .map(|channel| match channel {
+                // the tip rides inside the shared transaction, never as its own tx
+                SendChannel::Jito(JitoMode::Bundle) => SendChannel::Jito(JitoMode::BundleTipInTx),
+                channel => channel,
+            })
+            .filter(|channel| match tip_provider(channel) {
+                None => true,
+                Some(_) if tip_all => true,
+                Some(provider) => {
+                    // jito bundles and jito transactions share the jito tip
+                    if *tipped.get_or_insert(provider) == provider {
+                        return true;
+                    }
+                    warn!(
+                        "broadcast channel {:?} skipped, set BROADCAST_TIP_ALL to tip it as well",
+                        channel
+                    );
+                    false
+                }
+            })
+            .collect();
+        Self {
+            channels,
+            uuid_string,
+            timeout: Duration::from_secs(import_env_var_with_default("BROADCAST_TIMEOUT_SECS", 30)),
+        }
+    }
+
+    /// BROADCAST_CHANNELS, see `parse_channels`, and BROADCAST_TIP_ALL
+    pub fn from_env(uuid_string: Option<String>) -> Self {
+        let channels: String =
+            import_env_var_with_default("BROADCAST_CHANNELS", "rpc,jito".to_string());
+        Self::new(
+            parse_channels(&channels),
+            uuid_string,
+            import_env_var_with_default("BROADCAST_TIP_ALL", false),
+        )
+    }
+
+    pub fn channels(&self) -> &[SendChannel] {
+        &self.channels
+    }
+
+    /// one tip per paid provider kept, to be added as the last instructions of the
+    /// transaction before it is signed
+    pub async fn tip_instructions(&self, payer: &Pubkey) -> Result<Vec<Instruction>> {
+        let mut instructions = vec![];
+        if self
+            .channels
+            .iter()
+            .any(|channel| matches!(channel, SendChannel::Jito(_)))
+        {
+            let jito_client = get_jito_sdk(self.uuid_string.clone());
+            instructions.push(jito::get_tip_instruction(&jito_client, payer).await?);
+        }
+        if self.channels.contains(&SendChannel::NextBlock) {
+            instructions.push(NextBlockClient::from_env().tip_instruction(payer));
+        }
+        Ok(instructions)
+    }
+
+    pub async fn broadcast(&self, txn: &VersionedTransaction) -> Result<BroadcastResult> {
+        if self.channels.is_empty() {
+            return Err(anyhow!("no broadcast channels configured"));
+        }
+        let signature = txn.signatures[0];
+        let rpc_client = create_nonblocking_rpc_client().await?;
+        let submissions = join_all(
+            self.channels
+                .iter()
+                .map(|channel| self.submit(rpc_client.clone(), *channel, txn)),
+        )
+        .await;
+        for submission in submissions.iter() {
+            match &submission.result {
+                Ok(id) => info!(
+                    "broadcast {:?}: {}, elapsed: {:?}",
+                    submission.channel, id, submission.elapsed
+                ),
+                Err(err) => warn!("broadcast {:?} failed: {}", submission.channel, err),
+            }
+        }
+        if submissions
+            .iter()
+            .all(|submission| submission.result.is_err())
+        {
+            return Err(anyhow!("every broadcast channel rejected {}", signature));
+        }
+
+        let (slot, err) = self.wait_for_landing(&rpc_client, &signature).await?;
+        let landed_via = self.attribute(&submissions).await;
+        *LANDED_VIA
+            .lock()
+            .unwrap()
+            .entry(landed_via.map_or("unknown".to_string(), |channel| format!("{:?}", channel)))
+            .or_default() += 1;
+        info!(
+            "broadcast landed: {}, slot: {}, via: {:?}, err: {:?}",
+            signature, slot, landed_via, err
+        );
+        Ok(BroadcastResult {
+            signature,
+            slot,
+            err,
+            landed_via,
+            submissions,
+        })
+    }
+
+    async fn submit(
+        &self,
+        rpc_client: Arc<RpcClient>,
+        channel: SendChannel,
+        txn: &VersionedTransaction,
+    ) -> Submission {
+        let start_time = Instant::now();
+        let mut engine = None;
+        let result = match channel {
+            SendChannel::Rpc => rpc_client
+                .send_transaction_with_config(
+                    txn,
+                    RpcSendTransactionConfig {
+                        skip_preflight: true,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map(|signature| signature.to_string())
+                .map_err(Into::into),
+            SendChannel::Jito(JitoMode::Transaction) => {
+                let jito_client = match get_block_engines() {
+                    Some(block_engines) => {
+                        engine = block_engines.fastest().into_iter().next();
+                        self.jito_client(engine.as_ref())
+                    }
+                    None => get_jito_sdk(self.uuid_string.clone()),
+                };
+                jito::send_transaction(&jito_client, txn).await
+            }
+            SendChannel::Jito(_) => match get_block_engines() {
+                Some(block_engines) => block_engines
+                    .send_bundle(std::slice::from_ref(txn), self.uuid_string.as_deref())
+                    .await
+                    .map(|fan_out| {
+                        engine = fan_out.accepted.first().map(|(engine, _)| engine.clone());
+                        fan_out.bundle_id
+                    }),
+                None => {
+                    let jito_client = get_jito_sdk(self.uuid_string.clone());
+                    jito::send_bundle(
+                        &jito_client,
+                        std::slice::from_ref(txn),
+                        self.uuid_string.as_deref(),
+                    )
+                    .await
+                }
+            },
+            SendChannel::NextBlock => NextBlockClient::from_env()
+                .submit(txn)
+                .await
+                .map(|result| result.signature),
+            SendChannel::Broadcast => Err(anyhow!("nested broadcast")),
+        };
+        Submission {
+            channel,
+            result,
+            elapsed: start_time.elapsed(),
+            engine,
+        }
+    }
+
+    fn jito_client(&self, engine: Option<&BlockEngine>) -> jito::JitoClient {
+        match engine {
+            Some(engine) => engine.client(self.uuid_string.clone()),
+            None => get_jito_sdk(self.uuid_string.clone()),
+        }
+    }
+
+    async fn wait_for_landing(
+        &self,
+        rpc_client: &RpcClient,
+        signature: &Signature,
+    ) -> Result<(u64, Option<TransactionError>)> {
+        let start_time = Instant::now();
+        while start_time.elapsed() < self.timeout {
+            match rpc_client.get_signature_statuses(&[*signature]).await {
+                Ok(response) => {
+                    if let Some(Some(status)) = response.value.first() {
+                        if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                            return Ok((status.slot, status.err.clone()));
+                        }
+                    }
+                }
+                Err(err) => warn!("signature status fetch failed: {}", err),
+            }
+            sleep(Duration::from_millis(400)).await;
+        }
+        Err(anyhow!(
+            "{} not confirmed after {} secs",
+            signature,
+            self.timeout.as_secs()
+        ))
+    }
+
+    // a landed jito bundle shows up in getBundleStatuses of the engine that
+    // took it, the other channels carry the same bytes and can't be told
+    // apart on chain
+    async fn attribute(&self, submissions: &[Submission]) -> Option<SendChannel> {
+        for submission in submissions {
+            if let (SendChannel::Jito(JitoMode::BundleTipInTx), Ok(bundle_id)) =
+                (submission.channel, &submission.result)
+            {
+                let jito_client = self.jito_client(submission.engine.as_ref());
+                if let Ok(response) =
+                    jito::with_rate_limit(&jito_client.rate_limit_key(JitoRequest::Status), || {
+                        jito_client.get_bundle_statuses(vec![bundle_id.clone()])
+                    })
+                    .await
+                {
+                    let landed = response["result"]["value"]
+                        .as_array()
+                        .is_some_and(|statuses| statuses.iter().any(|s| !s.is_null()));
+                    if landed {
+                        return Some(submission.channel);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// comma separated rpc, jito (bundle), jito-tx and nextblock, unknown names
+/// are skipped
+pub fn parse_channels(value: &str) -> Vec<SendChannel> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| match name {
+            "rpc" => Some(SendChannel::Rpc),
+            "jito" => Some(SendChannel::Jito(JitoMode::BundleTipInTx)),
+            "jito-tx" => Some(SendChannel::Jito(JitoMode::Transaction)),
+            "nextblock" => Some(SendChannel::NextBlock),
+            other => {
+                warn!("unknown broadcast channel: {}", other);
+                None
+            }
+        })
+        .collect()
+}
+
+// who gets paid for landing through `channel`, None for plain rpc
+fn tip_provider(channel: &SendChannel) -> Option<&'static str> {
+    match channel {
+        SendChannel::Jito(_) => Some("jito"),
+        SendChannel::NextBlock => Some("nextblock"),
+        SendChannel::Rpc | SendChannel::Broadcast => None,
+    }
+}
+
+/// landings since start by the channel that provably landed them, "unknown"
+/// when it wasn't a jito bundle. these are counts, not per channel landing
+/// rates: every channel gets every transaction.
+pub fn get_landing_stats() -> HashMap<String, u64> {
+    LANDED_VIA.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_instruction;
+    use solana_sdk::{hash::Hash, signature::Keypair, signer::Signer};
+
+    use super::*;
+    use crate::{core::builder::TxBuilder, utils::http_stub::HttpStub};
+
+    const BUNDLE: SendChannel = SendChannel::Jito(JitoMode::BundleTipInTx);
+    const JITO_TX: SendChannel = SendChannel::Jito(JitoMode::Transaction);
+
+    fn signed_transaction() -> VersionedTransaction {
+        let payer = Keypair::new();
+        TxBuilder::new(&payer)
+            .instruction(system_instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1,
+            ))
+            .build_signed(Hash::new_unique())
+            .unwrap()
+    }
+
+    fn statuses(status: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","result":{{"context":{{"slot":100}},"value":[{}]}},"id":1}}"#,
+            status
+        )
+    }
+
+    fn broadcaster(timeout: Duration) -> Broadcaster {
+        Broadcaster {
+            timeout,
+            ..Broadcaster::new(vec![SendChannel::Rpc], None, false)
+        }
+    }
+
+    #[test]
+    fn parses_channel_names() {
+        assert_eq!(
+            parse_channels(" rpc,jito ,jito-tx,,nextblock,carrier-pigeon"),
+            vec![SendChannel::Rpc, BUNDLE, JITO_TX, SendChannel::NextBlock]
+        );
+        assert!(parse_channels("").is_empty());
+    }
+
+    #[test]
+    fn only_the_first_paid_provider_is_kept() {
+        let channels = vec![
+            SendChannel::Broadcast,
+            SendChannel::Rpc,
+            SendChannel::Jito(JitoMode::Bundle),
+            SendChannel::NextBlock,
+            JITO_TX,
+        ];
+        // both jito channels share the one jito tip
+        let broadcaster = Broadcaster::new(channels.clone(), None, false);
+        assert_eq!(broadcaster.channels(), [SendChannel::Rpc, BUNDLE, JITO_TX]);
+
+        let broadcaster = Broadcaster::new(channels, None, true);
+        assert_eq!(
+            broadcaster.channels(),
+            [SendChannel::Rpc, BUNDLE, SendChannel::NextBlock, JITO_TX]
+        );
+
+        let broadcaster = Broadcaster::new(vec![SendChannel::NextBlock, BUNDLE], None, false);
+        assert_eq!(broadcaster.channels(), [SendChannel::NextBlock]);
+    }
+
+    #[tokio::test]
+    async fn rpc_submission_returns_the_signature() {
+        let txn = signed_transaction();
+        let stub = HttpStub::start(
+            200,
+            &format!(
+                r#"{{"jsonrpc":"2.0","result":"{}","id":1}}"#,
+                txn.signatures[0]
+            ),
+            Duration::ZERO,
+        )
+        .await;
+        let rpc_client = Arc::new(RpcClient::new(stub.url.clone()));
+        let submission = broadcaster(Duration::from_secs(1))
+            .submit(rpc_client, SendChannel::Rpc, &txn)
+            .await;
+        assert_eq!(submission.result.unwrap(), txn.signatures[0].to_string());
+        assert_eq!(submission.engine, None);
+        let request = stub.requests()[0].json();
+        assert_eq!(request["method"], "sendTransaction");
+        assert_eq!(request["params"][1]["skipPreflight"], true);
+    }
+
+    #[tokio::test]
+    async fn landing_waits_for_confirmed() {
+        let stub = HttpStub::start(
+            200,
+            &statuses(
+                r#"{"slot":99,"confirmations":null,"err":null,"status":{"Ok":null},"confirmationStatus":"confirmed"}"#,
+            ),
+            Duration::ZERO,
+        )
+        .await;
+        let rpc_client = RpcClient::new(stub.url.clone());
+        let landed = broadcaster(Duration::from_secs(1))
+            .wait_for_landing(&rpc_client, &Signature::new_unique())
+            .await
+            .unwrap();
+        assert_eq!(landed, (99, None));
+    }
+
+    #[tokio::test]
+    async fn landing_reports_the_transaction_error() {
+        let stub = HttpStub::start(
+            200,
+            &statuses(
+                r#"{"slot":99,"confirmations":null,"err":{"InstructionError":[0,{"Custom":6001}]},"status":{"Err":{"InstructionError":[0,{"Custom":6001}]}},"confirmationStatus":"confirmed"}"#,
+            ),
+            Duration::ZERO,
+        )
+        .await;
+        let rpc_client = RpcClient::new(stub.url.clone());
+        let (slot, err) = broadcaster(Duration::from_secs(1))
+            .wait_for_landing(&rpc_client, &Signature::new_unique())
+            .await
+            .unwrap();
+        assert_eq!(slot, 99);
+        assert!(matches!(
+            err,
+            Some(TransactionError::InstructionError(0, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn landing_times_out_on_processed() {
+        let stub = HttpStub::start(
+            200,
+            &statuses(
+                r#"{"slot":99,"confirmations":0,"err":null,"status":{"Ok":null},"confirmationStatus":"processed"}"#,
+            ),
+            Duration::ZERO,
+        )
+        .await;
+        let rpc_client = RpcClient::new(stub.url.clone());
+        let result = broadcaster(Duration::from_millis(500))
+            .wait_for_landing(&rpc_client, &Signature::new_unique())
+            .await;
+        assert!(result.is_err());
+        assert!(!stub.requests().is_empty());
+    }
+
+    fn bundle_submission(engine: &HttpStub) -> Submission {
+        Submission {
+            channel: BUNDLE,
+            result: Ok("bundle-1".to_string()),
+            elapsed: Duration::ZERO,
+            engine: Some(BlockEngine::new("local", &engine.url)),
+        }
+    }
+
+    #[tokio::test]
+    async fn landing_is_attributed_to_a_landed_bundle() {
+        let engine = HttpStub::start(
+            200,
+            r#"{"jsonrpc":"2.0","result":{"context":{"slot":100},"value":[{"bundle_id":"bundle-1","transactions":[],"slot":99,"confirmation_status":"confirmed","err":{"Ok":null}}]},"id":1}"#,
+            Duration::ZERO,
+        )
+        .await;
+        let rpc = Submission {
+            channel: SendChannel::Rpc,
+            result: Ok(Signature::new_unique().to_string()),
+            elapsed: Duration::ZERO,
+            engine: None,
+        };
+        let landed_via = broadcaster(Duration::from_secs(1))
+            .attribute(&[rpc, bundle_submission(&engine)])
+            .await;
+        assert_eq!(landed_via, Some(BUNDLE));
+        // the status comes from the engine that accepted the bundle
+        let request = engine.requests()[0].json();
+        assert_eq!(request["method"], "getBundleStatuses");
+        assert_eq!(request["params"][0][0], "bundle-1");
+    }
+
+    #[tokio::test]
+    async fn landing_is_unattributed_without_a_bundle_status() {
+        let engine = HttpStub::start(
+            200,
+            r#"{"jsonrpc":"2.0","result":{"context":{"slot":100},"value":[null]},"id":1}"#,
+            Duration::ZERO,
+        )
+        .await;
+        let mut rejected = bundle_submission(&engine);
+        rejected.result = Err(anyhow!("bundle rejected"));
+        let broadcaster = broadcaster(Duration::from_secs(1));
+        assert_eq!(
+            broadcaster.attribute(&[bundle_submission(&engine)]).await,
+            None
+        );
+        assert_eq!(broadcaster.attribute(&[rejected]).await, None);
+        assert_eq!(engine.requests().len(), 1);
+    }
+}
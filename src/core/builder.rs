@@ -0,0 +1,217 @@
+use anyhow::{Result, anyhow};
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_program::system_instruction;
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::Instruction,
+    message::{VersionedMessage, v0},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::VersionedTransaction,
+};
+
+/// Assembles a v0 `VersionedTransaction` from a set of instructions.
+/// Building and signing are kept apart from sending so a transaction can be
+/// simulated, inspected or re-broadcast.
+#[derive(Clone)]
+pub struct TxBuilder<'a> {
+    payer: &'a Keypair,
+    signers: Vec<&'a Keypair>,
+    instructions: Vec<Instruction>,
+    unit_limit: Option<u32>,
+    unit_price: Option<u64>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    /// nonce account and its authority
+    nonce: Option<(Pubkey, Pubkey)>,
+}
+
+impl<'a> TxBuilder<'a> {
+    pub fn new(payer: &'a Keypair) -> Self {
+        Self {
+            payer,
+            signers: vec![],
+            instructions: vec![],
+            unit_limit: None,
+            unit_price: None,
+            lookup_tables: vec![],
+            nonce: None,
+        }
+    }
+
+    pub fn payer(&self) -> &'a Keypair {
+        self.payer
+    }
+
+    pub fn instruction(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    pub fn instructions(mut self, instructions: impl IntoIterator<Item = Instruction>) -> Self {
+        self.instructions.extend(instructions);
+        self
+    }
+
+    /// extra signer besides the payer
+    pub fn signer(mut self, signer: &'a Keypair) -> Self {
+        if signer.pubkey() != self.payer.pubkey()
+            && !self.signers.iter().any(|s| s.pubkey() == signer.pubkey())
+        {
+            self.signers.push(signer);
+        }
+        self
+    }
+
+    pub fn unit_limit(mut self, unit_limit: u32) -> Self {
+        self.unit_limit = Some(unit_limit);
+        self
+    }
+
+    /// micro-lamports per compute unit
+    pub fn unit_price(mut self, unit_price: u64) -> Self {
+        self.unit_price = Some(unit_price);
+        self
+    }
+
+    pub fn compute_budget(self, unit_limit: u32, unit_price: u64) -> Self {
+        self.unit_limit(unit_limit).unit_price(unit_price)
+    }
+
+    pub fn lookup_tables(mut self, lookup_tables: Vec<AddressLookupTableAccount>) -> Self {
+        self.lookup_tables = lookup_tables;
+        self
+    }
+
+    /// use a durable nonce instead of a recent blockhash. the blockhash passed
+    /// when building has to be the one stored in `nonce_account`, and
+    /// `authority` has to sign, either as payer or as an extra signer.
+    pub fn nonce(mut self, nonce_account: Pubkey, authority: Pubkey) -> Self {
+        self.nonce = Some((nonce_account, authority));
+        self
+    }
+
+    /// instructions in execution order, nonce advance first, then compute budget
+    pub fn all_instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::with_capacity(self.instructions.len() + 3);
+        if let Some((nonce_account, authority)) = &self.nonce {
+            instructions.push(system_instruction::advance_nonce_account(
+                nonce_account,
+                authority,
+            ));
+        }
+        if let Some(unit_price) = self.unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(unit_price));
+        }
+        if let Some(unit_limit) = self.unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+        }
+        instructions.extend(self.instructions.iter().cloned());
+        instructions
+    }
+
+    pub fn compile_message(&self, recent_blockhash: Hash) -> Result<VersionedMessage> {
+        let message = v0::Message::try_compile(
+            &self.payer.pubkey(),
+            &self.all_instructions(),
+            &self.lookup_tables,
+            recent_blockhash,
+        )
+        .map_err(|e| anyhow!("failed to compile v0 message: {}", e))?;
+        Ok(VersionedMessage::V0(message))
+    }
+
+    /// transaction with placeholder signatures, for simulation or signing elsewhere
+    pub fn build_unsigned(&self, recent_blockhash: Hash) -> Result<VersionedTransaction> {
+        let message = self.compile_message(recent_blockhash)?;
+        let num_signatures = message.header().num_required_signatures as usize;
+        Ok(VersionedTransaction {
+            signatures: vec![Signature::default(); num_signatures],
+            message,
+        })
+    }
+
+    pub fn build_signed(&self, recent_blockhash: Hash) -> Result<VersionedTransaction> {
+        let message = self.compile_message(recent_blockhash)?;
+        let mut signers: Vec<&Keypair> = vec![self.payer];
+        signers.extend(self.signers.iter().copied());
+        VersionedTransaction::try_new(message, &signers)
+            .map_err(|e| anyhow!("failed to sign transaction: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::system_program;
+
+    use super::*;
+
+    fn transfer(payer: &Keypair) -> Instruction {
+        system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)
+    }
+
+    #[test]
+    fn nonce_advance_then_compute_budget_then_instructions() {
+        let payer = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let user = transfer(&payer);
+        let builder = TxBuilder::new(&payer)
+            .instruction(user.clone())
+            .compute_budget(200_000, 1_000)
+            .nonce(nonce_account, payer.pubkey());
+        let instructions = builder.all_instructions();
+        assert_eq!(
+            instructions,
+            vec![
+                system_instruction::advance_nonce_account(&nonce_account, &payer.pubkey()),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                user,
+            ]
+        );
+        assert_eq!(instructions[0].program_id, system_program::ID);
+    }
+
+    #[test]
+    fn only_set_parts_are_added() {
+        let payer = Keypair::new();
+        let first = transfer(&payer);
+        let second = transfer(&payer);
+        let builder = TxBuilder::new(&payer)
+            .instructions([first.clone(), second.clone()])
+            .unit_limit(100_000);
+        assert_eq!(
+            builder.all_instructions(),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                first.clone(),
+                second.clone(),
+            ]
+        );
+        assert_eq!(
+            TxBuilder::new(&payer)
+                .instructions([first.clone(), second.clone()])
+                .all_instructions(),
+            vec![first, second]
+        );
+    }
+
+    #[test]
+    fn extra_signers_are_deduplicated() {
+        let payer = Keypair::new();
+        let other = Keypair::new();
+        let builder = TxBuilder::new(&payer)
+            .instruction(system_instruction::transfer(
+                &other.pubkey(),
+                &payer.pubkey(),
+                1,
+            ))
+            .signer(&payer)
+            .signer(&other)
+            .signer(&other);
+        let txn = builder.build_signed(Hash::new_unique()).unwrap();
+        assert_eq!(txn.signatures.len(), 2);
+        assert!(txn.verify_with_results().iter().all(|ok| *ok));
+    }
+}
@@ -0,0 +1,406 @@
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, pubkey::Pubkey, signature::Keypair, signature::Signature,
+    signer::Signer, transaction::VersionedTransaction,
+};
+
+use crate::{
+    core::{blockhash::get_latest_blockhash, builder::TxBuilder},
+    service::{
+        block_engine::get_block_engines,
+        jito::{
+            BundleOutcome, get_jito_sdk, get_tip_instruction, send_bundle,
+            wait_for_bundle_confirmation,
+        },
+    },
+    utils::jjj::{create_rpc_client, import_env_var_with_default},
+};
+
+// the block engine rejects bundles with more transactions
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+#[derive(Clone)]
+enum BundleEntry<'a> {
+    Builder(TxBuilder<'a>),
+    Signed(VersionedTransaction),
+}
+
+#[derive(Clone)]
+enum TipPlacement<'a> {
+    /// the transactions already pay the tip
+    None,
+    /// tip as the last instruction of the last transaction, paid by its payer
+    LastTransaction,
+    /// tip as its own transaction at the end of the bundle
+    Separate(&'a Keypair),
+}
+
+#[derive(Clone, Debug)]
+pub struct BundleResult {
+    pub bundle_id: String,
+    /// in bundle order, tip transaction included
+    pub signatures: Vec<Signature>,
+    pub outcome: BundleOutcome,
+}
+
+/// Up to five transactions, signed by any of our wallets, sent as one jito
+/// bundle. They either all land, in order, in the same block or none does.
+/// Every transaction is signed on the same blockhash, and the tip always
+/// ends up in the last transaction so it is only paid when everything lands.
+#[derive(Clone)]
+pub struct BundleBuilder<'a> {
+    entries: Vec<BundleEntry<'a>>,
+    tip: TipPlacement<'a>,
+    blockhash: Option<Hash>,
+    uuid_string: Option<String>,
+}
+
+impl<'a> BundleBuilder<'a> {
+    pub fn new(uuid_string: Option<String>) -> Self {
+        Self {
+            entries: vec![],
+            tip: TipPlacement::None,
+            blockhash: None,
+            uuid_string,
+        }
+    }
+
+    /// transaction to be signed by its own payer and signers
+    pub fn transaction(mut self, builder: TxBuilder<'a>) -> Self {
+        self.entries.push(BundleEntry::Builder(builder));
+        self
+    }
+
+    /// transaction signed elsewhere, its blockhash becomes the shared one
+    pub fn signed(mut self, txn: VersionedTransaction) -> Self {
+        self.entries.push(BundleEntry::Signed(txn));
+        self
+    }
+
+    pub fn tip_in_last_transaction(mut self) -> Self {
+        self.tip = TipPlacement::LastTransaction;
+        self
+    }
+
+    pub fn tip_transaction(mut self, payer: &'a Keypair) -> Self {
+        self.tip = TipPlacement::Separate(payer);
+        self
+    }
+
+    /// blockhash for the transactions still to be signed, the cached or else
+    /// the latest one is used when neither this nor a signed transaction sets it
+    pub fn blockhash(mut self, blockhash: Hash) -> Self {
+        self.blockhash = Some(blockhash);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() + matches!(self.tip, TipPlacement::Separate(_)) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn shared_blockhash(&self) -> Result<Hash> {
+        let mut blockhash = self.blockhash;
+        for entry in self.entries.iter() {
+            if let BundleEntry::Signed(txn) = entry {
+                let recent_blockhash = *txn.message.recent_blockhash();
+                match blockhash {
+                    Some(blockhash) if blockhash != recent_blockhash => {
+                        return Err(anyhow!(
+                            "BundleError: {} is signed on {}, the bundle on {}",
+                            txn.signatures[0],
+                            recent_blockhash,
+                            blockhash
+                        ));
+                    }
+                    _ => blockhash = Some(recent_blockhash),
+                }
+            }
+        }
+        match blockhash {
+            Some(blockhash) => Ok(blockhash),
+            // the cache, or rpc while it is empty, e.g. right after start
+            None => Ok(get_latest_blockhash(&create_rpc_client()?)?.0),
+        }
+    }
+
+    /// sign every transaction on the shared blockhash and place the tip
+    pub async fn build(&self) -> Result<Vec<VersionedTransaction>> {
+        self.check_size()?;
+        let tip_payer = self.tip_payer()?;
+        let blockhash = self.shared_blockhash()?;
+        let tip_instruction = match tip_payer {
+            Some(payer) => {
+                let jito_client = get_jito_sdk(self.uuid_string.clone());
+                Some(get_tip_instruction(&jito_client, &payer).await?)
+            }
+            None => None,
+        };
+        self.assemble(blockhash, tip_instruction)
+    }
+
+    fn check_size(&self) -> Result<()> {
+        if self.is_empty() {
+            return Err(anyhow!("BundleError: empty bundle"));
+        }
+        if self.len() > MAX_BUNDLE_TRANSACTIONS {
+            return Err(anyhow!(
+                "BundleError: {} transactions, at most {} fit in a bundle",
+                self.len(),
+                MAX_BUNDLE_TRANSACTIONS
+            ));
+        }
+        Ok(())
+    }
+
+    // who pays the tip, None when the transactions already do
+    fn tip_payer(&self) -> Result<Option<Pubkey>> {
+        Ok(match self.tip {
+            TipPlacement::None => None,
+            TipPlacement::LastTransaction => match self.entries.last() {
+                Some(BundleEntry::Builder(builder)) => Some(builder.payer().pubkey()),
+                _ => {
+                    return Err(anyhow!(
+                        "BundleError: can't add a tip to a transaction signed elsewhere"
+                    ));
+                }
+            },
+            TipPlacement::Separate(payer) => Some(payer.pubkey()),
+        })
+    }
+
+    fn assemble(
+        &self,
+        blockhash: Hash,
+        tip_instruction: Option<Instruction>,
+    ) -> Result<Vec<VersionedTransaction>> {
+        let last = self.entries.len() - 1;
+        let mut transactions = Vec::with_capacity(self.len());
+        for (index, entry) in self.entries.iter().enumerate() {
+            let txn = match entry {
+                BundleEntry::Builder(builder)
+                    if index == last && matches!(self.tip, TipPlacement::LastTransaction) =>
+                {
+                    builder
+                        .clone()
+                        .instructions(tip_instruction.clone())
+                        .build_signed(blockhash)?
+                }
+                BundleEntry::Builder(builder) => builder.build_signed(blockhash)?,
+                BundleEntry::Signed(txn) => txn.clone(),
+            };
+            transactions.push(txn);
+        }
+        if let TipPlacement::Separate(payer) = self.tip {
+            transactions.push(
+                TxBuilder::new(payer)
+                    .instructions(tip_instruction)
+                    .build_signed(blockhash)?,
+            );
+        }
+        Ok(transactions)
+    }
+
+    /// build, submit and follow the bundle. Invalid and Dropped bundles are
+    /// sent again up to JITO_BUNDLE_RETRIES times, any other outcome is
+    /// returned as is.
+    pub async fn send(&self) -> Result<BundleResult> {
+        let transactions = self.build().await?;
+        submit(&transactions, self.uuid_string.clone()).await
+    }
+}
+
+/// send signed transactions as one bundle, to the regional engines when
+/// configured, and wait for the outcome
+pub async fn submit(
+    transactions: &[VersionedTransaction],
+    uuid_string: Option<String>,
+) -> Result<BundleResult> {
+    let signatures: Vec<Signature> = transactions.iter().map(|txn| txn.signatures[0]).collect();
+    let retries = import_env_var_with_default("JITO_BUNDLE_RETRIES", 2);
+    let block_engines = get_block_engines();
+    let mut attempt = 0;
+    loop {
+        // fan out over the regional engines when configured, and follow
+        // the bundle on the fastest one that accepted it
+        let (bundle_id, status_client) = match &block_engines {
+            Some(block_engines) => {
+                let fan_out = block_engines
+                    .send_bundle(transactions, uuid_string.as_deref())
+                    .await?;
+                let status_client = fan_out.accepted[0].0.client(uuid_string.clone());
+                (fan_out.bundle_id, status_client)
+            }
+            None => {
+                let jito_client = get_jito_sdk(uuid_string.clone());
+                let bundle_id =
+                    send_bundle(&jito_client, transactions, uuid_string.as_deref()).await?;
+                (bundle_id, jito_client)
+            }
+        };
+        info!("bundle_id: {}", bundle_id);
+        let outcome = wait_for_bundle_confirmation(
+            &status_client,
+            &bundle_id,
+            Duration::from_millis(1000),
+            Duration::from_secs(10),
+        )
+        .await;
+        if let BundleOutcome::Landed { slot } = outcome {
+            info!("bundle landed: {}, slot: {:?}", bundle_id, slot);
+        }
+        // the same bytes can only land once, resending is safe
+        if outcome.is_retryable() && attempt < retries {
+            attempt += 1;
+            warn!(
+                "bundle {} {:?}, resending ({}/{})",
+                bundle_id, outcome, attempt, retries
+            );
+            continue;
+        }
+        return Ok(BundleResult {
+            bundle_id,
+            signatures,
+            outcome,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_instruction;
+
+    use super::*;
+
+    fn transfer(payer: &Keypair) -> TxBuilder<'_> {
+        TxBuilder::new(payer).instruction(system_instruction::transfer(
+            &payer.pubkey(),
+            &Pubkey::new_unique(),
+            1,
+        ))
+    }
+
+    fn tip(payer: &Keypair) -> Instruction {
+        system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000)
+    }
+
+    fn last_instruction(txn: &VersionedTransaction) -> (Pubkey, Vec<u8>) {
+        let message = &txn.message;
+        let instruction = message.instructions().last().unwrap();
+        let keys = message.static_account_keys();
+        (
+            keys[instruction.program_id_index as usize],
+            instruction.data.clone(),
+        )
+    }
+
+    #[tokio::test]
+    async fn size_is_checked_before_anything_is_fetched() {
+        let payer = Keypair::new();
+        assert!(BundleBuilder::new(None).build().await.is_err());
+
+        let mut bundle = BundleBuilder::new(None);
+        for _ in 0..MAX_BUNDLE_TRANSACTIONS {
+            bundle = bundle.transaction(transfer(&payer));
+        }
+        assert_eq!(bundle.len(), MAX_BUNDLE_TRANSACTIONS);
+        // the separate tip transaction counts too
+        let with_tip = bundle.clone().tip_transaction(&payer);
+        assert_eq!(with_tip.len(), MAX_BUNDLE_TRANSACTIONS + 1);
+        assert!(with_tip.build().await.is_err());
+        assert!(bundle.transaction(transfer(&payer)).build().await.is_err());
+    }
+
+    #[test]
+    fn tip_goes_into_the_last_transaction() {
+        let (first, second, third) = (Keypair::new(), Keypair::new(), Keypair::new());
+        let blockhash = Hash::new_unique();
+        let signed = transfer(&second).build_signed(blockhash).unwrap();
+        let bundle = BundleBuilder::new(None)
+            .transaction(transfer(&first))
+            .signed(signed.clone())
+            .transaction(transfer(&third))
+            .tip_in_last_transaction();
+        assert_eq!(bundle.tip_payer().unwrap(), Some(third.pubkey()));
+        // the blockhash of the signed transaction is shared
+        assert_eq!(bundle.shared_blockhash().unwrap(), blockhash);
+
+        let tip = tip(&third);
+        let transactions = bundle.assemble(blockhash, Some(tip.clone())).unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(
+            transactions[0].message.static_account_keys()[0],
+            first.pubkey()
+        );
+        assert_eq!(transactions[1], signed);
+        assert_eq!(
+            transactions[2].message.static_account_keys()[0],
+            third.pubkey()
+        );
+        assert_eq!(
+            last_instruction(&transactions[2]),
+            (tip.program_id, tip.data)
+        );
+        assert!(
+            transactions
+                .iter()
+                .all(|txn| *txn.message.recent_blockhash() == blockhash)
+        );
+    }
+
+    #[test]
+    fn separate_tip_comes_last() {
+        let (payer, tipper) = (Keypair::new(), Keypair::new());
+        let blockhash = Hash::new_unique();
+        let bundle = BundleBuilder::new(None)
+            .transaction(transfer(&payer))
+            .transaction(transfer(&payer))
+            .tip_transaction(&tipper)
+            .blockhash(blockhash);
+        assert_eq!(bundle.tip_payer().unwrap(), Some(tipper.pubkey()));
+        let tip = tip(&tipper);
+        let transactions = bundle.assemble(blockhash, Some(tip.clone())).unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(
+            transactions[2].message.static_account_keys()[0],
+            tipper.pubkey()
+        );
+        assert_eq!(transactions[2].message.instructions().len(), 1);
+        assert_eq!(
+            last_instruction(&transactions[2]),
+            (tip.program_id, tip.data)
+        );
+        // the others are left as they were built
+        assert_eq!(transactions[1].message.instructions().len(), 1);
+    }
+
+    #[test]
+    fn signed_transactions_must_agree_on_the_blockhash() {
+        let payer = Keypair::new();
+        let signed = transfer(&payer).build_signed(Hash::new_unique()).unwrap();
+        let bundle = BundleBuilder::new(None)
+            .blockhash(Hash::new_unique())
+            .signed(signed.clone());
+        assert!(bundle.shared_blockhash().is_err());
+
+        // no tip can be added to a transaction signed elsewhere
+        let bundle = BundleBuilder::new(None)
+            .transaction(transfer(&payer))
+            .signed(signed)
+            .tip_in_last_transaction();
+        assert!(bundle.tip_payer().is_err());
+        assert_eq!(
+            BundleBuilder::new(None)
+                .transaction(transfer(&payer))
+                .tip_payer()
+                .unwrap(),
+            None
+        );
+    }
+}
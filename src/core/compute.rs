@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
+    hash::{Hash as _, Hasher},
+    sync::{LazyLock, RwLock},
+};
+
+use anyhow::{Result, anyhow};
+use log::info;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{hash::Hash, instruction::Instruction, pubkey::Pubkey, system_program};
+
+use crate::{
+    core::{builder::TxBuilder, token, tx},
+    utils::jjj::import_env_var_with_default,
+};
+
+// highest limit a transaction may request, used while simulating
+const MAX_UNIT_LIMIT: u32 = 1_400_000;
+
+static COMPUTE_UNIT_CACHE: LazyLock<ComputeUnitCache> = LazyLock::new(|| {
+    ComputeUnitCache::new(
+        import_env_var_with_default("CU_SIMULATE", false),
+        import_env_var_with_default("CU_MARGIN_PERCENT", 20),
+    )
+});
+
+/// Compute unit limits taken from simulating a transaction, cached per
+/// instruction shape so the hot path only simulates the first swap of a kind.
+/// The shape covers the program ids, so it is also per venue.
+pub struct ComputeUnitCache {
+    enabled: bool,
+    margin_percent: u32,
+    limits: RwLock<HashMap<u64, u32>>,
+}
+
+impl ComputeUnitCache {
+    pub fn new(enabled: bool, margin_percent: u32) -> Self {
+        Self {
+            enabled,
+            margin_percent,
+            limits: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// units_consumed plus the margin, or None when simulation is turned off
+    pub fn unit_limit(
+        &self,
+        client: &RpcClient,
+        builder: &TxBuilder,
+        recent_blockhash: Hash,
+    ) -> Result<Option<u32>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let instructions = builder.all_instructions();
+        let shape = instruction_shape(&instructions);
+        if let Some(unit_limit) = self.limits.read().unwrap().get(&shape) {
+            return Ok(Some(*unit_limit));
+        }
+
+        let txn = builder
+            .clone()
+            .unit_limit(MAX_UNIT_LIMIT)
+            .build_unsigned(recent_blockhash)?;
+        let result = tx::simulate(client, &txn)?;
+        if let Some(err) = result.err {
+            return Err(anyhow!(
+                "simulation failed: {:?}, logs: {:?}",
+                err,
+                result.logs
+            ));
+        }
+        let units_consumed = result
+            .units_consumed
+            .ok_or(anyhow!("simulation returned no units_consumed"))?;
+        let unit_limit = (units_consumed * (100 + self.margin_percent as u64))
+            .div_ceil(100)
+            .min(MAX_UNIT_LIMIT as u64) as u32;
+        info!(
+            "compute units: consumed: {}, limit: {}, shape: {:x}",
+            units_consumed, unit_limit, shape
+        );
+        if is_cacheable(&instructions) {
+            self.limits.write().unwrap().insert(shape, unit_limit);
+        }
+        Ok(Some(unit_limit))
+    }
+}
+
+// program, leading data byte and sizes of every instruction, plus the
+// programs it passes along as accounts; amounts change between swaps but the
+// work done by the programs does not. a swap cpis into whichever token
+// program the mint uses and token-2022 costs more, so that is part of it
+fn instruction_shape(instructions: &[Instruction]) -> u64 {
+    let programs: HashSet<Pubkey> = instructions
+        .iter()
+        .map(|instruction| instruction.program_id)
+        .collect();
+    let mut hasher = DefaultHasher::new();
+    for instruction in instructions {
+        if instruction.program_id == solana_compute_budget_interface::ID {
+            continue;
+        }
+        instruction.program_id.hash(&mut hasher);
+        instruction.data.first().hash(&mut hasher);
+        instruction.data.len().hash(&mut hasher);
+        instruction.accounts.len().hash(&mut hasher);
+        for (index, meta) in instruction.accounts.iter().enumerate() {
+            if is_program_account(&meta.pubkey) || programs.contains(&meta.pubkey) {
+                (index, meta.pubkey).hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
+
+// executable accounts a swap commonly hands to the program it calls
+fn is_program_account(pubkey: &Pubkey) -> bool {
+    token::is_token_program(pubkey)
+        || *pubkey == system_program::ID
+        || *pubkey == spl_associated_token_account::ID
+}
+
+// an idempotent ata create costs far more when it creates the account than
+// when it already exists, with the same shape either way, so such
+// transactions are simulated every time
+fn is_cacheable(instructions: &[Instruction]) -> bool {
+    !instructions.iter().any(|instruction| {
+        instruction.program_id == spl_associated_token_account::ID
+            && instruction.data.first() == Some(&1)
+    })
+}
+
+/// process wide cache, see CU_SIMULATE and CU_MARGIN_PERCENT
+pub fn get_compute_unit_cache() -> &'static ComputeUnitCache {
+    &COMPUTE_UNIT_CACHE
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+    use spl_associated_token_account::instruction::{
+        create_associated_token_account, create_associated_token_account_idempotent,
+    };
+
+    use super::*;
+
+    #[test]
+    fn idempotent_ata_create_is_not_cached() {
+        let (payer, owner, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let idempotent =
+            create_associated_token_account_idempotent(&payer, &owner, &mint, &spl_token::ID);
+        let create = create_associated_token_account(&payer, &owner, &mint, &spl_token::ID);
+        assert!(!is_cacheable(&[idempotent]));
+        assert!(is_cacheable(&[create]));
+        assert!(is_cacheable(&[]));
+    }
+
+    fn swap(token_program: Pubkey, amount: u64) -> Instruction {
+        Instruction::new_with_bytes(
+            Pubkey::new_from_array([7; 32]),
+            &[
+                &[102u8, 6, 61, 18, 1, 218, 235, 234][..],
+                &amount.to_le_bytes(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(Pubkey::new_unique(), true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        )
+    }
+
+    #[test]
+    fn shape_ignores_amounts_and_user_accounts() {
+        assert_eq!(
+            instruction_shape(&[swap(spl_token::ID, 1)]),
+            instruction_shape(&[swap(spl_token::ID, 2)])
+        );
+    }
+
+    #[test]
+    fn shape_follows_token_program() {
+        assert_ne!(
+            instruction_shape(&[swap(spl_token::ID, 1)]),
+            instruction_shape(&[swap(spl_token_2022::ID, 1)])
+        );
+
+        let cache = ComputeUnitCache::new(true, 20);
+        let legacy = instruction_shape(&[swap(spl_token::ID, 1)]);
+        cache.limits.write().unwrap().insert(legacy, 60_000);
+        let limits = cache.limits.read().unwrap();
+        assert_eq!(
+            limits.get(&instruction_shape(&[swap(spl_token::ID, 5)])),
+            Some(&60_000)
+        );
+        assert_eq!(
+            limits.get(&instruction_shape(&[swap(spl_token_2022::ID, 5)])),
+            None
+        );
+    }
+}
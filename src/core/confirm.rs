@@ -0,0 +1,335 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use log::{info, warn};
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::{RpcSendTransactionConfig, RpcSignatureSubscribeConfig},
+    rpc_response::RpcSignatureResult,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, signature::Signature,
+    transaction::TransactionError, transaction::VersionedTransaction,
+};
+use solana_transaction_status_client_types::TransactionStatus;
+use tokio::time::{Instant, sleep};
+
+use crate::{
+    core::builder::TxBuilder,
+    utils::jjj::{
+        create_nonblocking_rpc_client, import_env_var_with_default, import_env_var_with_option,
+    },
+};
+
+/// builds and signs the same transaction again on a new blockhash
+pub type ResignFn<'a> = dyn Fn(Hash) -> Result<VersionedTransaction> + Sync + 'a;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxOutcome {
+    Landed {
+        signature: Signature,
+        slot: u64,
+    },
+    /// landed, but the program returned an error
+    Failed {
+        signature: Signature,
+        err: TransactionError,
+    },
+    /// the last blockhash expired before any signature landed
+    Expired,
+}
+
+/// Sends a transaction over rpc and follows it until it lands or its
+/// blockhash expires. Landing is watched with `signatureSubscribe` on RPC_WSS,
+/// `getSignatureStatuses` polling covers a missing or broken websocket.
+pub struct ConfirmationTracker {
+    rpc_client: Arc<RpcClient>,
+    wss_url: Option<String>,
+    poll_interval: Duration,
+    rebroadcast_interval: Duration,
+    max_resigns: u32,
+}
+
+impl ConfirmationTracker {
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        wss_url: Option<String>,
+        poll_interval: Duration,
+        rebroadcast_interval: Duration,
+        max_resigns: u32,
+    ) -> Self {
+        Self {
+            rpc_client,
+            wss_url,
+            poll_interval,
+            rebroadcast_interval,
+            max_resigns,
+        }
+    }
+
+    /// RPC_WSS, CONFIRM_POLL_MS, CONFIRM_REBROADCAST_MS and CONFIRM_MAX_RESIGNS.
+    /// one re-sign by default, 0 reports Expired as soon as the first
+    /// blockhash runs out.
+    pub fn from_env(rpc_client: Arc<RpcClient>) -> Self {
+        Self::new(
+            rpc_client,
+            import_env_var_with_option("RPC_WSS").and_then(|url| url.into_string().ok()),
+            Duration::from_millis(import_env_var_with_default("CONFIRM_POLL_MS", 500)),
+            Duration::from_millis(import_env_var_with_default("CONFIRM_REBROADCAST_MS", 2000)),
+            import_env_var_with_default("CONFIRM_MAX_RESIGNS", 1),
+        )
+    }
+
+    /// send `txn`, re-broadcast it until `last_valid_block_height` passes and,
+    /// when `resign` is given, start over on a new blockhash up to
+    /// `max_resigns` times. an expired transaction can no longer land, so
+    /// re-signing never executes the trade twice.
+    pub async fn send_and_confirm(
+        &self,
+        mut txn: VersionedTransaction,
+        mut last_valid_block_height: u64,
+        resign: Option<&ResignFn<'_>>,
+    ) -> Result<TxOutcome> {
+        let mut resigns = 0;
+        loop {
+            if let Some(outcome) = self.track(&txn, last_valid_block_height).await? {
+                return Ok(outcome);
+            }
+            let Some(resign) = resign.filter(|_| resigns < self.max_resigns) else {
+                return Ok(TxOutcome::Expired);
+            };
+            resigns += 1;
+            let (blockhash, block_height) = self
+                .rpc_client
+                .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+                .await?;
+            let expired = txn.signatures[0];
+            txn = resign(blockhash)?;
+            last_valid_block_height = block_height;
+            info!(
+                "{} expired, re-signed as {} ({}/{})",
+                expired, txn.signatures[0], resigns, self.max_resigns
+            );
+        }
+    }
+
+    // None once the blockhash of `txn` expired without it landing
+    async fn track(
+        &self,
+        txn: &VersionedTransaction,
+        last_valid_block_height: u64,
+    ) -> Result<Option<TxOutcome>> {
+        let signature = txn.signatures[0];
+        let mut notification = Box::pin(self.subscribe(signature));
+        let mut last_sent = Instant::now();
+        self.send(txn).await?;
+        loop {
+            tokio::select! {
+                outcome = &mut notification => return Ok(Some(outcome)),
+                _ = sleep(self.poll_interval) => {}
+            }
+            if let Some(outcome) = self.poll(&signature).await {
+                return Ok(Some(outcome));
+            }
+            // a failed fetch only delays the expiry check, the transaction
+            // may still land meanwhile
+            match self
+                .rpc_client
+                .get_block_height_with_commitment(CommitmentConfig::confirmed())
+                .await
+            {
+                Ok(block_height) if block_height > last_valid_block_height => {
+                    // one last look, it may have landed in the final valid block
+                    return Ok(self.poll(&signature).await);
+                }
+                Ok(_) => {}
+                Err(err) => warn!("block height fetch failed: {}", err),
+            }
+            if last_sent.elapsed() >= self.rebroadcast_interval {
+                if let Err(err) = self.send(txn).await {
+                    warn!("re-broadcast of {} failed: {}", signature, err);
+                }
+                last_sent = Instant::now();
+            }
+        }
+    }
+
+    async fn send(&self, txn: &VersionedTransaction) -> Result<()> {
+        let config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            // we re-broadcast ourselves
+            max_retries: Some(0),
+            ..Default::default()
+        };
+        let signature = self
+            .rpc_client
+            .send_transaction_with_config(txn, config)
+            .await?;
+        info!("signature: {}", signature);
+        Ok(())
+    }
+
+    async fn poll(&self, signature: &Signature) -> Option<TxOutcome> {
+        let response = self
+            .rpc_client
+            .get_signature_statuses(&[*signature])
+            .await
+            .inspect_err(|err| warn!("signature status fetch failed: {}", err))
+            .ok()?;
+        status_outcome(signature, response.value.into_iter().next()?)
+    }
+
+    // resolves with the notification, or never when there is no websocket
+    async fn subscribe(&self, signature: Signature) -> TxOutcome {
+        if let Some(wss_url) = &self.wss_url {
+            match subscribe_signature(wss_url, &signature).await {
+                Ok(outcome) => return outcome,
+                Err(err) => warn!("signatureSubscribe failed, polling only: {}", err),
+            }
+        }
+        std::future::pending().await
+    }
+}
+
+/// sign `builder` on the latest blockhash and follow it with the tracker from
+/// env, re-signing on expiry. for setup transactions like lookup tables and
+/// nonce accounts, anything but landing is an error.
+pub async fn send_and_confirm_builder(builder: &TxBuilder<'_>) -> Result<Signature> {
+    let rpc_client = create_nonblocking_rpc_client().await?;
+    let (blockhash, last_valid_block_height) = rpc_client
+        .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+        .await?;
+    let txn = builder.build_signed(blockhash)?;
+    let resign = |blockhash: Hash| builder.build_signed(blockhash);
+    let outcome = ConfirmationTracker::from_env(rpc_client)
+        .send_and_confirm(txn, last_valid_block_height, Some(&resign))
+        .await?;
+    landed_signature(outcome)
+}
+
+fn landed_signature(outcome: TxOutcome) -> Result<Signature> {
+    match outcome {
+        TxOutcome::Landed { signature, .. } => Ok(signature),
+        TxOutcome::Failed { signature, err } => Err(anyhow!("{} failed: {}", signature, err)),
+        TxOutcome::Expired => Err(anyhow!("transaction expired before landing")),
+    }
+}
+
+fn landed_outcome(signature: &Signature, slot: u64, err: Option<TransactionError>) -> TxOutcome {
+    match err {
+        None => TxOutcome::Landed {
+            signature: *signature,
+            slot,
+        },
+        Some(err) => TxOutcome::Failed {
+            signature: *signature,
+            err,
+        },
+    }
+}
+
+// None until the status reaches confirmed
+fn status_outcome(signature: &Signature, status: Option<TransactionStatus>) -> Option<TxOutcome> {
+    let status = status?;
+    if !status.satisfies_commitment(CommitmentConfig::confirmed()) {
+        return None;
+    }
+    Some(landed_outcome(signature, status.slot, status.err))
+}
+
+async fn subscribe_signature(wss_url: &str, signature: &Signature) -> Result<TxOutcome> {
+    let pubsub_client = PubsubClient::new(wss_url).await?;
+    let config = RpcSignatureSubscribeConfig {
+        commitment: Some(CommitmentConfig::confirmed()),
+        enable_received_notification: Some(false),
+    };
+    let (mut stream, unsubscribe) = pubsub_client
+        .signature_subscribe(signature, Some(config))
+        .await?;
+    let response = stream
+        .next()
+        .await
+        .ok_or_else(|| anyhow!("signature subscription closed"))?;
+    drop(stream);
+    unsubscribe().await;
+    match response.value {
+        RpcSignatureResult::ProcessedSignature(result) => {
+            Ok(landed_outcome(signature, response.context.slot, result.err))
+        }
+        RpcSignatureResult::ReceivedSignature(_) => {
+            Err(anyhow!("unexpected received notification"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::InstructionError;
+    use solana_transaction_status_client_types::TransactionConfirmationStatus;
+
+    use super::*;
+
+    fn status(
+        confirmation_status: TransactionConfirmationStatus,
+        err: Option<TransactionError>,
+    ) -> TransactionStatus {
+        TransactionStatus {
+            slot: 99,
+            confirmations: None,
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status: Some(confirmation_status),
+        }
+    }
+
+    #[test]
+    fn statuses_map_to_outcomes() {
+        let signature = Signature::new_unique();
+        assert_eq!(status_outcome(&signature, None), None);
+        assert_eq!(
+            status_outcome(
+                &signature,
+                Some(status(TransactionConfirmationStatus::Processed, None))
+            ),
+            None
+        );
+        assert_eq!(
+            status_outcome(
+                &signature,
+                Some(status(TransactionConfirmationStatus::Confirmed, None))
+            ),
+            Some(TxOutcome::Landed {
+                signature,
+                slot: 99
+            })
+        );
+        let err = TransactionError::InstructionError(0, InstructionError::Custom(6001));
+        assert_eq!(
+            status_outcome(
+                &signature,
+                Some(status(
+                    TransactionConfirmationStatus::Finalized,
+                    Some(err.clone())
+                ))
+            ),
+            Some(TxOutcome::Failed { signature, err })
+        );
+    }
+
+    #[test]
+    fn only_landing_is_success() {
+        let signature = Signature::new_unique();
+        assert_eq!(
+            landed_signature(landed_outcome(&signature, 1, None)).unwrap(),
+            signature
+        );
+        let failed = landed_outcome(
+            &signature,
+            1,
+            Some(TransactionError::InsufficientFundsForFee),
+        );
+        assert!(landed_signature(failed).is_err());
+        assert!(landed_signature(TxOutcome::Expired).is_err());
+    }
+}
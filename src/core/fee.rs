@@ -0,0 +1,213 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::Result;
+use log::{info, warn};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::utils::jjj::import_env_var_with_default;
+
+// getRecentPrioritizationFees takes at most 128 accounts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+static PRIORITY_FEE_ESTIMATOR: LazyLock<PriorityFeeEstimator> = LazyLock::new(|| {
+    PriorityFeeEstimator::new(PriorityFeeConfig {
+        percentile: import_env_var_with_default("PRIORITY_FEE_PERCENTILE", 75),
+        min_unit_price: import_env_var_with_default("PRIORITY_FEE_MIN", 1_000),
+        max_unit_price: import_env_var_with_default("PRIORITY_FEE_MAX", 2_000_000),
+        smoothing: import_env_var_with_default("PRIORITY_FEE_SMOOTHING", 0.3),
+    })
+});
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PriorityFeeConfig {
+    /// percentile of the recent per-slot fees to pay, 0..=100
+    pub percentile: u8,
+    /// micro-lamports per compute unit
+    pub min_unit_price: u64,
+    pub max_unit_price: u64,
+    /// weight of the newest sample in the moving average, 1.0 disables smoothing
+    pub smoothing: f64,
+}
+
+/// Compute unit price from `getRecentPrioritizationFees` over the accounts a
+/// transaction write-locks, so we pay what the competition for those
+/// accounts currently pays.
+pub struct PriorityFeeEstimator {
+    config: PriorityFeeConfig,
+    average: Mutex<Option<f64>>,
+}
+
+impl PriorityFeeEstimator {
+    pub fn new(config: PriorityFeeConfig) -> Self {
+        Self {
+            config,
+            average: Mutex::new(None),
+        }
+    }
+
+    /// micro-lamports per compute unit for a transaction made of `instructions`
+    pub fn estimate(&self, client: &RpcClient, instructions: &[Instruction]) -> Result<u64> {
+        let accounts = writable_accounts(instructions);
+        let fees: Vec<u64> = client
+            .get_recent_prioritization_fees(&accounts)?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        let samples = fees.len();
+        let unit_price = self.unit_price(fees);
+        info!(
+            "priority fee: accounts: {}, samples: {}, unit price: {}",
+            accounts.len(),
+            samples,
+            unit_price
+        );
+        Ok(unit_price)
+    }
+
+    /// unit price from the per-slot fees: the configured percentile, folded
+    /// into the moving average and clamped. no samples keeps the average as is
+    pub fn unit_price(&self, fees: Vec<u64>) -> u64 {
+        match percentile(fees, self.config.percentile) {
+            Some(sample) => self.smooth(sample),
+            None => self.clamp(self.average.lock().unwrap().unwrap_or(0.0) as u64),
+        }
+    }
+
+    /// like `estimate`, falling back to the last average (or the floor) when
+    /// the rpc call fails
+    pub fn estimate_or_floor(&self, client: &RpcClient, instructions: &[Instruction]) -> u64 {
+        self.estimate(client, instructions).unwrap_or_else(|err| {
+            warn!("priority fee estimate failed, using the last one: {}", err);
+            self.clamp(self.average.lock().unwrap().unwrap_or(0.0) as u64)
+        })
+    }
+
+    fn smooth(&self, sample: u64) -> u64 {
+        let mut average = self.average.lock().unwrap();
+        let smoothing = self.config.smoothing.clamp(0.0, 1.0);
+        let next = match *average {
+            Some(previous) => previous + smoothing * (sample as f64 - previous),
+            None => sample as f64,
+        };
+        *average = Some(next);
+        self.clamp(next.round() as u64)
+    }
+
+    fn clamp(&self, unit_price: u64) -> u64 {
+        unit_price.clamp(
+            self.config.min_unit_price,
+            self.config.max_unit_price.max(self.config.min_unit_price),
+        )
+    }
+}
+
+/// accounts the instructions write-lock, signers excluded since the payer
+/// says nothing about contention
+fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut seen = HashSet::new();
+    instructions
+        .iter()
+        .flat_map(|instruction| instruction.accounts.iter())
+        .filter(|meta| meta.is_writable && !meta.is_signer)
+        .filter(|meta| seen.insert(meta.pubkey))
+        .map(|meta| meta.pubkey)
+        .take(MAX_FEE_ACCOUNTS)
+        .collect()
+}
+
+// nearest-rank percentile, slots without any fee count as zero
+fn percentile(mut fees: Vec<u64>, percentile: u8) -> Option<u64> {
+    if fees.is_empty() {
+        return None;
+    }
+    fees.sort_unstable();
+    let rank = (percentile.min(100) as usize * fees.len()).div_ceil(100);
+    Some(fees[rank.saturating_sub(1)])
+}
+
+/// process wide estimator, see the PRIORITY_FEE_* env vars
+pub fn get_priority_fee_estimator() -> &'static PriorityFeeEstimator {
+    &PRIORITY_FEE_ESTIMATOR
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::instruction::AccountMeta;
+
+    use super::*;
+
+    fn estimator(smoothing: f64) -> PriorityFeeEstimator {
+        PriorityFeeEstimator::new(PriorityFeeConfig {
+            percentile: 75,
+            min_unit_price: 1_000,
+            max_unit_price: 100_000,
+            smoothing,
+        })
+    }
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let fees: Vec<u64> = (1..=10).rev().map(|fee| fee * 1_000).collect();
+        assert_eq!(percentile(fees.clone(), 75), Some(8_000));
+        assert_eq!(percentile(fees.clone(), 50), Some(5_000));
+        assert_eq!(percentile(fees.clone(), 100), Some(10_000));
+        assert_eq!(percentile(fees.clone(), 200), Some(10_000));
+        assert_eq!(percentile(fees, 0), Some(1_000));
+        assert_eq!(percentile(vec![7], 75), Some(7));
+        assert_eq!(percentile(vec![], 75), None);
+    }
+
+    #[test]
+    fn unit_price_is_clamped() {
+        assert_eq!(estimator(1.0).unit_price(vec![0, 0, 0, 0]), 1_000);
+        assert_eq!(estimator(1.0).unit_price(vec![5_000_000]), 100_000);
+        assert_eq!(estimator(1.0).unit_price(vec![20_000]), 20_000);
+    }
+
+    #[test]
+    fn empty_samples_keep_the_average() {
+        let estimator = estimator(0.5);
+        assert_eq!(estimator.unit_price(vec![]), 1_000);
+        assert_eq!(estimator.unit_price(vec![40_000]), 40_000);
+        assert_eq!(estimator.unit_price(vec![]), 40_000);
+    }
+
+    #[test]
+    fn samples_are_smoothed() {
+        let estimator = estimator(0.5);
+        // the first sample seeds the average
+        assert_eq!(estimator.unit_price(vec![40_000]), 40_000);
+        assert_eq!(estimator.unit_price(vec![20_000]), 30_000);
+        assert_eq!(estimator.unit_price(vec![20_000]), 25_000);
+        // the average is kept unclamped so a spike decays from its real value
+        assert_eq!(estimator.unit_price(vec![1_000_000]), 100_000);
+        assert_eq!(estimator.unit_price(vec![0]), 100_000);
+        assert_eq!(estimator.unit_price(vec![0]), 100_000);
+        assert_eq!(estimator.unit_price(vec![0]), 64_063);
+    }
+
+    #[test]
+    fn only_contended_accounts_are_sampled() {
+        let payer = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program,
+            &[],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                AccountMeta::new(pool, false),
+            ],
+        );
+        assert_eq!(
+            writable_accounts(&[instruction.clone(), instruction]),
+            vec![pool]
+        );
+    }
+}
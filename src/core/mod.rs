@@ -1,2 +1,12 @@
+pub mod alt;
+pub mod ata;
+pub mod blockhash;
+pub mod broadcast;
+pub mod builder;
+pub mod bundle;
+pub mod compute;
+pub mod confirm;
+pub mod fee;
+pub mod nonce;
 pub mod token;
 pub mod tx;
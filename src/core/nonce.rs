@@ -0,0 +1,509 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{LazyLock, RwLock},
+};
+
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use log::{info, warn};
+use solana_client::{
+    nonce_utils::{data_from_account, get_account_with_commitment},
+    rpc_client::RpcClient,
+};
+use solana_program::system_instruction::{self, SystemInstruction};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, instruction::Instruction,
+    nonce::state::State, pubkey::Pubkey, signature::Keypair, signature::Signature, signer::Signer,
+    system_program, transaction::VersionedTransaction,
+};
+use tokio::time::Instant;
+
+use crate::{
+    core::{
+        blockhash::MAX_PROCESSING_AGE,
+        builder::TxBuilder,
+        confirm::{ConfirmationTracker, TxOutcome, send_and_confirm_builder},
+        fee::get_priority_fee_estimator,
+        tx::get_unit_limit,
+    },
+    utils::jjj::create_nonblocking_rpc_client,
+};
+
+static PRESIGNED_STORE: LazyLock<PresignedStore> = LazyLock::new(PresignedStore::new);
+
+/// What a nonce account currently holds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NonceInfo {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    /// blockhash a transaction using this nonce has to be signed with
+    pub blockhash: Hash,
+    pub lamports: u64,
+}
+
+/// nonce accounts are derived from the wallet, so they can be found again
+/// without storing their addresses
+pub fn nonce_address(wallet: &Pubkey, index: u32) -> Result<Pubkey> {
+    Ok(Pubkey::create_with_seed(
+        wallet,
+        &nonce_seed(index),
+        &system_program::ID,
+    )?)
+}
+
+fn nonce_seed(index: u32) -> String {
+    format!("nonce-{}", index)
+}
+
+/// create the nonce account `index` of `keypair`, which also is its
+/// authority. does nothing when the account already exists.
+pub async fn create_nonce_account(
+    client: &RpcClient,
+    keypair: &Keypair,
+    index: u32,
+) -> Result<Pubkey> {
+    let wallet = keypair.pubkey();
+    let nonce_account = nonce_address(&wallet, index)?;
+    if client
+        .get_account_with_commitment(&nonce_account, CommitmentConfig::confirmed())?
+        .value
+        .is_some()
+    {
+        info!("nonce account {} already exists", nonce_account);
+        return Ok(nonce_account);
+    }
+    let lamports = client.get_minimum_balance_for_rent_exemption(State::size())?;
+    let instructions = system_instruction::create_nonce_account_with_seed(
+        &wallet,
+        &nonce_account,
+        &wallet,
+        &nonce_seed(index),
+        &wallet,
+        lamports,
+    );
+    send_and_confirm(TxBuilder::new(keypair).instructions(instructions)).await?;
+    info!("nonce account created: {}", nonce_account);
+    Ok(nonce_account)
+}
+
+/// top up a nonce account, anything above rent exemption can be withdrawn
+/// again by the authority
+pub async fn fund_nonce_account(
+    keypair: &Keypair,
+    nonce_account: &Pubkey,
+    lamports: u64,
+) -> Result<()> {
+    let instruction = system_instruction::transfer(&keypair.pubkey(), nonce_account, lamports);
+    send_and_confirm(TxBuilder::new(keypair).instruction(instruction)).await
+}
+
+pub fn get_nonce(client: &RpcClient, nonce_account: &Pubkey) -> Result<NonceInfo> {
+    let account = get_account_with_commitment(client, nonce_account, CommitmentConfig::confirmed())
+        .map_err(|err| anyhow!("NonceError: {}: {}", nonce_account, err))?;
+    nonce_info(nonce_account, &account)
+}
+
+fn nonce_info(nonce_account: &Pubkey, account: &Account) -> Result<NonceInfo> {
+    let data = data_from_account(account)
+        .map_err(|err| anyhow!("NonceError: {}: {}", nonce_account, err))?;
+    Ok(NonceInfo {
+        address: *nonce_account,
+        authority: data.authority,
+        blockhash: data.blockhash(),
+        lamports: account.lamports,
+    })
+}
+
+/// nonce accounts of `wallet` from index 0 up to the first missing one. any
+/// other failure is an error rather than a shorter list
+pub fn get_nonce_accounts(client: &RpcClient, wallet: &Pubkey) -> Result<Vec<NonceInfo>> {
+    let mut nonces = vec![];
+    for index in 0.. {
+        let nonce_account = nonce_address(wallet, index)?;
+        let account = client
+            .get_account_with_commitment(&nonce_account, CommitmentConfig::confirmed())
+            .map_err(|err| anyhow!("NonceError: {}: {}", nonce_account, err))?
+            .value;
+        match account {
+            Some(account) => nonces.push(nonce_info(&nonce_account, &account)?),
+            None => break,
+        }
+    }
+    Ok(nonces)
+}
+
+async fn send_and_confirm(builder: TxBuilder<'_>) -> Result<()> {
+    let signature = send_and_confirm_builder(&builder).await?;
+    info!("nonce tx: {}", signature);
+    Ok(())
+}
+
+/// A transaction signed against a durable nonce, valid until the nonce
+/// is advanced.
+#[derive(Clone, Debug)]
+pub struct PresignedTx {
+    pub id: String,
+    pub mint: Pubkey,
+    pub nonce_account: Pubkey,
+    pub txn: VersionedTransaction,
+    pub created_at: Instant,
+}
+
+impl PresignedTx {
+    /// base64 of the signed transaction, for handing it to another process
+    pub fn encode(&self) -> Result<String> {
+        Ok(STANDARD.encode(bincode::serialize(&self.txn)?))
+    }
+
+    /// blockhash the transaction was signed with, i.e. the nonce it expects
+    pub fn nonce_blockhash(&self) -> Hash {
+        *self.txn.message.recent_blockhash()
+    }
+}
+
+/// Pre-signed transactions, usually exits, waiting for their trigger.
+/// Each one should use its own nonce account: firing any transaction that
+/// advances a nonce invalidates every other one signed on it.
+#[derive(Default)]
+pub struct PresignedStore {
+    entries: RwLock<HashMap<String, PresignedTx>>,
+}
+
+impl PresignedStore {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// sign `instructions` on the current value of `nonce_account` and keep
+    /// the transaction under `id`. `keypair` pays and has to be the nonce
+    /// authority.
+    pub fn presign(
+        &self,
+        client: &RpcClient,
+        keypair: &Keypair,
+        id: &str,
+        mint: Pubkey,
+        instructions: Vec<Instruction>,
+        nonce_account: Pubkey,
+    ) -> Result<PresignedTx> {
+        let nonce = get_nonce(client, &nonce_account)?;
+        if nonce.authority != keypair.pubkey() {
+            return Err(anyhow!(
+                "NonceError: {} is owned by {}, not {}",
+                nonce_account,
+                nonce.authority,
+                keypair.pubkey()
+            ));
+        }
+        let txn = TxBuilder::new(keypair)
+            .nonce(nonce_account, nonce.authority)
+            .compute_budget(
+                get_unit_limit(),
+                get_priority_fee_estimator().estimate_or_floor(client, &instructions),
+            )
+            .instructions(instructions)
+            .build_signed(nonce.blockhash)?;
+        let presigned = PresignedTx {
+            id: id.to_string(),
+            mint,
+            nonce_account,
+            txn,
+            created_at: Instant::now(),
+        };
+        self.add(presigned.clone());
+        info!(
+            "presigned {}: {}, nonce: {}",
+            id, presigned.txn.signatures[0], nonce_account
+        );
+        Ok(presigned)
+    }
+
+    /// keep a transaction signed elsewhere, see `PresignedTx::encode`
+    pub fn import(&self, id: &str, mint: Pubkey, encoded: &str) -> Result<PresignedTx> {
+        let txn: VersionedTransaction = bincode::deserialize(&STANDARD.decode(encoded)?)?;
+        let nonce_account = advanced_nonce_account(&txn)
+            .ok_or_else(|| anyhow!("NonceError: {} doesn't start with a nonce advance", id))?;
+        let presigned = PresignedTx {
+            id: id.to_string(),
+            mint,
+            nonce_account,
+            txn,
+            created_at: Instant::now(),
+        };
+        self.add(presigned.clone());
+        Ok(presigned)
+    }
+
+    pub fn add(&self, presigned: PresignedTx) {
+        self.entries
+            .write()
+            .unwrap()
+            .insert(presigned.id.clone(), presigned);
+    }
+
+    pub fn get(&self, id: &str) -> Option<PresignedTx> {
+        self.entries.read().unwrap().get(id).cloned()
+    }
+
+    pub fn remove(&self, id: &str) -> Option<PresignedTx> {
+        self.entries.write().unwrap().remove(id)
+    }
+
+    /// every transaction kept for `mint`
+    pub fn for_mint(&self, mint: &Pubkey) -> Vec<PresignedTx> {
+        self.entries
+            .read()
+            .unwrap()
+            .values()
+            .filter(|presigned| presigned.mint == *mint)
+            .cloned()
+            .collect()
+    }
+
+    /// send the transaction kept under `id` and wait for it to land. the
+    /// entry stays in the store until the nonce moves, so a transaction that
+    /// wasn't seen yet can be fired again.
+    pub async fn fire(&self, id: &str) -> Result<FireOutcome> {
+        let presigned = self
+            .get(id)
+            .ok_or_else(|| anyhow!("NonceError: no presigned tx {}", id))?;
+        let signature = presigned.txn.signatures[0];
+        let rpc_client = create_nonblocking_rpc_client().await?;
+        if current_nonce(&rpc_client, &presigned.nonce_account).await?
+            != presigned.nonce_blockhash()
+        {
+            // an earlier fire may have landed it after all
+            return self.settle(&rpc_client, &presigned).await;
+        }
+        let block_height = rpc_client
+            .get_block_height_with_commitment(CommitmentConfig::confirmed())
+            .await?;
+        info!("firing presigned {}: {}", id, signature);
+        // a durable transaction doesn't expire, the tracker just needs a
+        // point to stop re-broadcasting, as long as a blockhash would last
+        let outcome = ConfirmationTracker::from_env(rpc_client.clone())
+            .send_and_confirm(
+                presigned.txn.clone(),
+                block_height + MAX_PROCESSING_AGE,
+                None,
+            )
+            .await?;
+        if outcome != TxOutcome::Expired {
+            self.remove(id);
+            return Ok(FireOutcome::Settled(outcome));
+        }
+        // not seen within the tracking window, only a moved nonce means it
+        // can't land anymore
+        if current_nonce(&rpc_client, &presigned.nonce_account).await?
+            == presigned.nonce_blockhash()
+        {
+            info!("presigned {} still pending, kept: {}", id, signature);
+            return Ok(FireOutcome::Pending { signature });
+        }
+        self.settle(&rpc_client, &presigned).await
+    }
+
+    // the nonce of `presigned` moved: report its own landing, or fail when
+    // another transaction used the nonce. the entry is dropped either way.
+    async fn settle(
+        &self,
+        rpc_client: &solana_client::nonblocking::rpc_client::RpcClient,
+        presigned: &PresignedTx,
+    ) -> Result<FireOutcome> {
+        self.remove(&presigned.id);
+        let signature = presigned.txn.signatures[0];
+        let status = rpc_client
+            .get_signature_statuses_with_history(&[signature])
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .flatten()
+            .filter(|status| status.satisfies_commitment(CommitmentConfig::confirmed()));
+        match status {
+            Some(status) => Ok(FireOutcome::Settled(match status.err {
+                None => TxOutcome::Landed {
+                    signature,
+                    slot: status.slot,
+                },
+                Some(err) => TxOutcome::Failed { signature, err },
+            })),
+            None => Err(anyhow!(
+                "NonceError: nonce {} advanced without {} landing",
+                presigned.nonce_account,
+                presigned.id
+            )),
+        }
+    }
+
+    /// fire `id` once `trigger` resolves, e.g. a price or timeout watch
+    pub async fn fire_on<F: Future<Output = ()>>(
+        &self,
+        id: &str,
+        trigger: F,
+    ) -> Result<FireOutcome> {
+        trigger.await;
+        let outcome = self.fire(id).await;
+        if let Err(err) = &outcome {
+            warn!("presigned {} failed to fire: {}", id, err);
+        }
+        outcome
+    }
+}
+
+/// What firing a pre-signed transaction came to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FireOutcome {
+    /// landed or failed on chain, the entry was removed
+    Settled(TxOutcome),
+    /// not seen on chain and the nonce is unchanged, so it can still land.
+    /// the entry is kept and can be fired again.
+    Pending { signature: Signature },
+}
+
+async fn current_nonce(
+    rpc_client: &solana_client::nonblocking::rpc_client::RpcClient,
+    nonce_account: &Pubkey,
+) -> Result<Hash> {
+    let account = rpc_client
+        .get_account_with_commitment(nonce_account, CommitmentConfig::confirmed())
+        .await?
+        .value
+        .ok_or_else(|| anyhow!("NonceError: {} not found", nonce_account))?;
+    Ok(data_from_account(&account)
+        .map_err(|err| anyhow!("NonceError: {}: {}", nonce_account, err))?
+        .blockhash())
+}
+
+// the nonce account of a durable transaction, None unless its first
+// instruction is a system advance_nonce_account
+fn advanced_nonce_account(txn: &VersionedTransaction) -> Option<Pubkey> {
+    let account_keys = txn.message.static_account_keys();
+    let instruction = txn.message.instructions().first()?;
+    if *instruction.program_id(account_keys) != system_program::ID {
+        return None;
+    }
+    match bincode::deserialize(&instruction.data).ok()? {
+        SystemInstruction::AdvanceNonceAccount => {}
+        _ => return None,
+    }
+    account_keys
+        .get(*instruction.accounts.first()? as usize)
+        .copied()
+}
+
+/// process wide store of pre-signed transactions
+pub fn get_presigned_store() -> &'static PresignedStore {
+    &PRESIGNED_STORE
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use solana_sdk::nonce::state::{Data, DurableNonce, Versions};
+
+    use super::*;
+    use crate::utils::http_stub::HttpStub;
+
+    fn nonce_data(authority: &Pubkey) -> Data {
+        Data::new(
+            *authority,
+            DurableNonce::from_blockhash(&Hash::new_unique()),
+            5_000,
+        )
+    }
+
+    fn nonce_account(state: State) -> Account {
+        Account {
+            lamports: 1_447_680,
+            data: bincode::serialize(&Versions::new(state)).unwrap(),
+            owner: system_program::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn nonce_info_reads_initialized_accounts() {
+        let (address, authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = nonce_data(&authority);
+        let info = nonce_info(&address, &nonce_account(State::Initialized(data.clone()))).unwrap();
+        assert_eq!(
+            info,
+            NonceInfo {
+                address,
+                authority,
+                blockhash: data.blockhash(),
+                lamports: 1_447_680,
+            }
+        );
+
+        assert!(nonce_info(&address, &nonce_account(State::Uninitialized)).is_err());
+        let mut foreign = nonce_account(State::Initialized(data));
+        foreign.owner = Pubkey::new_unique();
+        assert!(nonce_info(&address, &foreign).is_err());
+    }
+
+    async fn nonce_accounts(body: &str) -> Result<Vec<NonceInfo>> {
+        let stub = HttpStub::start(200, body, Duration::ZERO).await;
+        let url = stub.url.clone();
+        tokio::task::spawn_blocking(move || {
+            get_nonce_accounts(&RpcClient::new(url), &Pubkey::new_unique())
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn nonce_scan_stops_at_the_first_missing_account() {
+        let nonces = nonce_accounts(
+            r#"{"jsonrpc":"2.0","result":{"context":{"slot":1},"value":null},"id":1}"#,
+        )
+        .await
+        .unwrap();
+        assert!(nonces.is_empty());
+    }
+
+    #[tokio::test]
+    async fn nonce_scan_propagates_rpc_errors() {
+        let result = nonce_accounts(
+            r#"{"jsonrpc":"2.0","error":{"code":-32005,"message":"Node is behind"},"id":1}"#,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    fn encoded(builder: TxBuilder) -> String {
+        STANDARD
+            .encode(bincode::serialize(&builder.build_signed(Hash::default()).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn import_requires_nonce_advance_first() {
+        let keypair = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let transfer = system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1);
+        let store = PresignedStore::new();
+
+        let durable = TxBuilder::new(&keypair)
+            .nonce(nonce_account, keypair.pubkey())
+            .instruction(transfer.clone());
+        let presigned = store
+            .import("durable", Pubkey::new_unique(), &encoded(durable))
+            .unwrap();
+        assert_eq!(presigned.nonce_account, nonce_account);
+
+        // a plain transfer first also names a system account at index 0
+        let plain = TxBuilder::new(&keypair).instruction(transfer);
+        assert!(
+            store
+                .import("plain", Pubkey::new_unique(), &encoded(plain))
+                .is_err()
+        );
+        assert!(store.get("plain").is_none());
+    }
+}
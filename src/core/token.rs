@@ -1,6 +1,8 @@
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 use spl_token_2022::{
-    extension::StateWithExtensionsOwned,
+    extension::{
+        BaseStateWithExtensions, StateWithExtensionsOwned, transfer_fee::TransferFeeConfig,
+    },
     state::{Account, Mint},
 };
 use spl_token_client::{
@@ -9,18 +11,23 @@ use spl_token_client::{
 };
 use std::sync::Arc;
 
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::ID || *program_id == spl_token_2022::ID
+}
+
 pub fn get_associated_token_address(
     client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
     keypair: Arc<Keypair>,
     address: &Pubkey,
     owner: &Pubkey,
+    token_program: &Pubkey,
 ) -> Pubkey {
     let token_client = Token::new(
         Arc::new(ProgramRpcClient::new(
             client.clone(),
             ProgramRpcClientSendTransaction,
         )),
-        &spl_token::ID,
+        token_program,
         address,
         None,
         Arc::new(Keypair::from_bytes(&keypair.to_bytes()).expect("failed to copy keypair")),
@@ -45,7 +52,7 @@ pub async fn get_account_info(
         .ok_or(TokenError::AccountNotFound)
         .inspect_err(|err| println!("get_account_info: {} {}: mint {}", account, err, address))?;
 
-    if account.owner != spl_token::ID {
+    if !is_token_program(&account.owner) {
         return Err(TokenError::AccountInvalidOwner);
     }
     let account = StateWithExtensionsOwned::<Account>::unpack(account.data)?;
@@ -72,7 +79,7 @@ pub async fn get_mint_info(
         .ok_or(TokenError::AccountNotFound)
         .inspect_err(|err| println!("{} {}: mint {}", address, err, address))?;
 
-    if account.owner != spl_token::ID {
+    if !is_token_program(&account.owner) {
         return Err(TokenError::AccountInvalidOwner);
     }
 
@@ -86,3 +93,138 @@ pub async fn get_mint_info(
 
     mint_result
 }
+
+/// owner program of `address`, either spl-token or token-2022
+pub async fn get_token_program(
+    client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    address: &Pubkey,
+) -> TokenResult<Pubkey> {
+    let program_client = Arc::new(ProgramRpcClient::new(
+        client.clone(),
+        ProgramRpcClientSendTransaction,
+    ));
+    let account = program_client
+        .get_account(*address)
+        .await
+        .map_err(TokenError::Client)?
+        .ok_or(TokenError::AccountNotFound)?;
+
+    if !is_token_program(&account.owner) {
+        return Err(TokenError::AccountInvalidOwner);
+    }
+    Ok(account.owner)
+}
+
+pub fn has_transfer_fee(mint: &StateWithExtensionsOwned<Mint>) -> bool {
+    mint.get_extension::<TransferFeeConfig>().is_ok()
+}
+
+/// fee withheld when `amount` is transferred, zero for mints without the extension
+pub fn get_transfer_fee(mint: &StateWithExtensionsOwned<Mint>, epoch: u64, amount: u64) -> u64 {
+    mint.get_extension::<TransferFeeConfig>()
+        .ok()
+        .and_then(|config| config.calculate_epoch_fee(epoch, amount))
+        .unwrap_or(0)
+}
+
+/// fee to add on top so that exactly `amount` arrives after the transfer
+pub fn get_inverse_transfer_fee(
+    mint: &StateWithExtensionsOwned<Mint>,
+    epoch: u64,
+    amount: u64,
+) -> u64 {
+    mint.get_extension::<TransferFeeConfig>()
+        .ok()
+        .and_then(|config| config.calculate_inverse_epoch_fee(epoch, amount))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use solana_program::program_pack::Pack;
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        transfer_fee::TransferFee,
+    };
+
+    use super::*;
+
+    /// 1% capped at 5_000 until epoch 10, then 2% capped at 1_000_000
+    pub(crate) fn mint_with_transfer_fee() -> StateWithExtensionsOwned<Mint> {
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.older_transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: 5_000.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        config.newer_transfer_fee = TransferFee {
+            epoch: 10.into(),
+            maximum_fee: 1_000_000.into(),
+            transfer_fee_basis_points: 200.into(),
+        };
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        StateWithExtensionsOwned::<Mint>::unpack(data).unwrap()
+    }
+
+    fn plain_mint() -> StateWithExtensionsOwned<Mint> {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        StateWithExtensionsOwned::<Mint>::unpack(data).unwrap()
+    }
+
+    #[test]
+    fn no_fee_without_extension() {
+        let mint = plain_mint();
+        assert!(!has_transfer_fee(&mint));
+        assert_eq!(get_transfer_fee(&mint, 5, 1_000_000), 0);
+        assert_eq!(get_inverse_transfer_fee(&mint, 5, 1_000_000), 0);
+    }
+
+    #[test]
+    fn transfer_fee_rounds_up_and_caps() {
+        let mint = mint_with_transfer_fee();
+        assert!(has_transfer_fee(&mint));
+        assert_eq!(get_transfer_fee(&mint, 5, 10_000), 100);
+        assert_eq!(get_transfer_fee(&mint, 5, 1), 1);
+        assert_eq!(get_transfer_fee(&mint, 5, 0), 0);
+        // 1% of 1_000_000 is over the 5_000 maximum
+        assert_eq!(get_transfer_fee(&mint, 5, 1_000_000), 5_000);
+    }
+
+    #[test]
+    fn transfer_fee_follows_epoch() {
+        let mint = mint_with_transfer_fee();
+        assert_eq!(get_transfer_fee(&mint, 9, 10_000), 100);
+        assert_eq!(get_transfer_fee(&mint, 10, 10_000), 200);
+        assert_eq!(get_transfer_fee(&mint, 11, 1_000_000), 20_000);
+    }
+
+    #[test]
+    fn inverse_fee_leaves_the_amount() {
+        let mint = mint_with_transfer_fee();
+        // 10_000 gross leaves 9_900 after 1%
+        assert_eq!(get_inverse_transfer_fee(&mint, 5, 9_900), 100);
+        assert_eq!(get_inverse_transfer_fee(&mint, 5, 1_000_000), 5_000);
+        assert_eq!(get_inverse_transfer_fee(&mint, 10, 9_800), 200);
+        for amount in [1, 9_900, 123_456, 1_000_000] {
+            let gross = amount + get_inverse_transfer_fee(&mint, 5, amount);
+            assert_eq!(gross - get_transfer_fee(&mint, 5, gross), amount);
+        }
+    }
+}
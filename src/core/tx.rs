@@ -1,126 +1,215 @@
-use log::info;
-use std::{env, sync::Arc, time::Duration};
+use log::{info, warn};
+use std::env;
 
-use anyhow::Result;
-use jito_sdk_rust::JitoJsonRpcSDK;
+use anyhow::{Result, anyhow};
 use solana_client::rpc_client::RpcClient;
+use solana_client::{
+    rpc_config::RpcSimulateTransactionConfig, rpc_response::RpcSimulateTransactionResult,
+};
 use solana_sdk::{
-    instruction::Instruction,
-    signature::Keypair,
-    signer::Signer,
-    transaction::{Transaction, VersionedTransaction},
+    address_lookup_table::AddressLookupTableAccount, commitment_config::CommitmentConfig,
+    hash::Hash, instruction::Instruction, signature::Keypair, signer::Signer,
+    transaction::VersionedTransaction,
 };
-use solana_system_transaction as system_transaction;
-use spl_token::ui_amount_to_amount;
-
-use solana_compute_budget_interface::ComputeBudgetInstruction;
 
-use crate::service::jito::{get_jito_sdk, get_tip_value, wait_for_bundle_confirmation};
-use crate::utils::jjj::{create_nonblocking_rpc_client, import_env_var};
-use serde_json::json;
-use solana_sdk::pubkey::Pubkey;
+use crate::core::{
+    blockhash::get_latest_blockhash,
+    broadcast::Broadcaster,
+    builder::TxBuilder,
+    bundle::BundleBuilder,
+    compute::get_compute_unit_cache,
+    confirm::{ConfirmationTracker, ResignFn, TxOutcome},
+    fee::get_priority_fee_estimator,
+};
+use crate::service::jito::{
+    BundleOutcome, JitoMode, get_jito_sdk, get_tip_instruction, send_transaction,
+};
+use crate::service::nextblock::NextBlockClient;
+use crate::utils::jjj::create_nonblocking_rpc_client;
 use std::str::FromStr;
 use tokio::time::Instant;
-use uuid::uuid;
-
-// prioritization fee = UNIT_PRICE * UNIT_LIMIT
-fn get_unit_price() -> u64 {
-    env::var("UNIT_PRICE")
-        .ok()
-        .and_then(|v| u64::from_str(&v).ok())
-        .unwrap_or(1)
-}
 
-fn get_unit_limit() -> u32 {
+// prioritization fee = unit price * unit limit, the price comes from core::fee
+// and the limit from core::compute, UNIT_LIMIT is used when not simulating
+pub(crate) fn get_unit_limit() -> u32 {
     env::var("UNIT_LIMIT")
         .ok()
         .and_then(|v| u32::from_str(&v).ok())
         .unwrap_or(300_000)
 }
 
+/// Where a signed transaction is submitted, chosen per trade.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SendChannel {
+    #[default]
+    Rpc,
+    Jito(JitoMode),
+    NextBlock,
+    /// every channel in BROADCAST_CHANNELS at once, see core::broadcast
+    Broadcast,
+}
+
 pub async fn new_signed_and_send(
     client: &RpcClient,
     keypair: &Keypair,
-    mut instructions: Vec<Instruction>,
-    use_jito: bool,
+    instructions: Vec<Instruction>,
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    channel: SendChannel,
     uuid_string: Option<String>,
 ) -> Result<Vec<String>> {
-    let unit_price = get_unit_price();
-    let unit_limit = get_unit_limit();
-    // If not using Jito, manually set the compute unit price and limit
-    if !use_jito {
-        let modify_compute_units = ComputeBudgetInstruction::set_compute_unit_price(unit_price);
-        let add_priority_fee = ComputeBudgetInstruction::set_compute_unit_limit(unit_limit);
-        instructions.insert(0, modify_compute_units);
-        instructions.insert(1, add_priority_fee);
+    // jito is paid through the tip, every other channel gets a priority fee
+    // and, when simulation works, a fitted unit limit
+    let unit_price = match channel {
+        SendChannel::Jito(_) => None,
+        SendChannel::Rpc | SendChannel::NextBlock | SendChannel::Broadcast => {
+            Some(get_priority_fee_estimator().estimate_or_floor(client, &instructions))
+        }
+    };
+    let mut builder = TxBuilder::new(keypair)
+        .instructions(instructions)
+        .lookup_tables(lookup_tables);
+    let (recent_blockhash, last_valid_block_height) = get_latest_blockhash(client)?;
+    // the tip goes last so it only pays out when the swap went through
+    match channel {
+        SendChannel::Jito(jito_mode) if jito_mode.tip_in_tx() => {
+            let jito_client = get_jito_sdk(uuid_string.clone());
+            builder =
+                builder.instruction(get_tip_instruction(&jito_client, &keypair.pubkey()).await?);
+        }
+        SendChannel::NextBlock => {
+            builder =
+                builder.instruction(NextBlockClient::from_env().tip_instruction(&keypair.pubkey()));
+        }
+        SendChannel::Broadcast => {
+            builder = builder.instructions(
+                Broadcaster::from_env(uuid_string.clone())
+                    .tip_instructions(&keypair.pubkey())
+                    .await?,
+            );
+        }
+        _ => {}
     }
-    // send init tx
-    let recent_blockhash = client.get_latest_blockhash()?;
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&keypair.pubkey()),
-        &vec![keypair],
-        recent_blockhash,
+    // simulated with the tip so its units are part of the limit
+    if let Some(unit_price) = unit_price {
+        builder = builder.unit_price(unit_price);
+        let unit_limit = get_compute_unit_cache()
+            .unit_limit(client, &builder, recent_blockhash)
+            .unwrap_or_else(|err| {
+                warn!("compute unit simulation failed: {}", err);
+                None
+            })
+            .unwrap_or_else(get_unit_limit);
+        builder = builder.unit_limit(unit_limit);
+    }
+    let txn = builder.build_signed(recent_blockhash)?;
+    log_transaction(&txn);
+    let resign = |blockhash: Hash| builder.build_signed(blockhash);
+    send_signed(
+        keypair,
+        txn,
+        last_valid_block_height,
+        channel,
+        uuid_string,
+        Some(&resign),
+    )
+    .await
+}
+
+pub fn simulate(
+    client: &RpcClient,
+    txn: &VersionedTransaction,
+) -> Result<RpcSimulateTransactionResult> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    };
+    let result = client.simulate_transaction_with_config(txn, config)?.value;
+    info!(
+        "simulate: err: {:?}, units_consumed: {:?}",
+        result.err, result.units_consumed
+    );
+    Ok(result)
+}
+
+pub fn log_transaction(txn: &VersionedTransaction) {
+    info!(
+        "tx: {}, instructions: {}, accounts: {}, message size: {} bytes",
+        txn.signatures[0],
+        txn.message.instructions().len(),
+        txn.message.static_account_keys().len(),
+        txn.message.serialize().len()
     );
+}
 
+/// send an already signed transaction over `channel`. with `JitoMode::Bundle`
+/// the tip is sent as a second transaction, the other jito modes and
+/// nextblock expect the tip to be part of `txn` already. rpc sends are
+/// followed until they land, `resign` lets them move to a new blockhash.
+pub async fn send_signed(
+    keypair: &Keypair,
+    txn: VersionedTransaction,
+    last_valid_block_height: u64,
+    channel: SendChannel,
+    uuid_string: Option<String>,
+    resign: Option<&ResignFn<'_>>,
+) -> Result<Vec<String>> {
     let start_time = Instant::now();
     let mut txs = vec![];
-    if use_jito {
-        // jito
-
-        let jito_sdk = get_jito_sdk(uuid_string.clone());
-        let jito_client = Arc::new(jito_sdk);
-        let tip_account = jito_client.get_random_tip_account().await?;
-        let tip_account = Pubkey::from_str(&tip_account)?;
-        // let jito_client = Arc::new(JitoRpcClient::new(format!(
-        //     "{}/api/v1/bundles",
-        //     *jito::BLOCK_ENGINE_URL
-        // )));
-        let tip = get_tip_value();
-        let tip_lamports = ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS);
-        info!(
-            "tip account: {}, tip(sol): {}, lamports: {}",
-            tip_account, tip, tip_lamports
-        );
-        // tip tx
-        let transactions: Vec<VersionedTransaction> = vec![
-            VersionedTransaction::from(txn),
-            VersionedTransaction::from(system_transaction::transfer(
-                keypair,
-                &tip_account,
-                tip_lamports,
-                recent_blockhash,
-            )),
-        ];
-
-        let bundle = json!(transactions);
-        let bundle_id = jito_client
-            .send_bundle(Some(bundle), uuid_string.as_deref())
-            .await?;
-        let bundle_id = bundle_id.to_string();
-        info!("bundle_id: {}", bundle_id);
-
-        txs = wait_for_bundle_confirmation(
-            move |id: String| {
-                let client = Arc::clone(&jito_client);
-                async move {
-                    let response = client.get_bundle_statuses(vec![id]).await;
-                    let statuses = response.inspect_err(|err| {
-                        info!("Error fetching bundle status: {:?}", err);
-                    })?;
-                    Ok(statuses)
+    if let SendChannel::Jito(jito_mode) = channel {
+        match jito_mode {
+            JitoMode::Transaction => {
+                let jito_client = get_jito_sdk(uuid_string.clone());
+                let sig = send_transaction(&jito_client, &txn).await?;
+                info!("jito signature: {}", sig);
+                txs.push(sig);
+            }
+            JitoMode::Bundle | JitoMode::BundleTipInTx => {
+                let mut bundle = BundleBuilder::new(uuid_string.clone()).signed(txn);
+                if jito_mode == JitoMode::Bundle {
+                    bundle = bundle.tip_transaction(keypair);
+                }
+                let result = bundle.send().await?;
+                match result.outcome {
+                    BundleOutcome::Landed { .. } => {
+                        txs = result
+                            .signatures
+                            .iter()
+                            .map(|signature| signature.to_string())
+                            .collect();
+                    }
+                    outcome => {
+                        return Err(anyhow!("bundle {}: {:?}", result.bundle_id, outcome));
+                    }
                 }
-            },
-            bundle_id,
-            Duration::from_millis(1000),
-            Duration::from_secs(10),
-        )
-        .await?;
+            }
+        }
+    } else if channel == SendChannel::Broadcast {
+        let result = Broadcaster::from_env(uuid_string).broadcast(&txn).await?;
+        if let Some(err) = result.err {
+            return Err(anyhow!("{} landed with error: {:?}", result.signature, err));
+        }
+        txs.push(result.signature.to_string());
+    } else if channel == SendChannel::NextBlock {
+        let result = NextBlockClient::from_env().submit(&txn).await?;
+        txs.push(result.signature);
     } else {
         let aaa = create_nonblocking_rpc_client().await?;
-        let sig = aaa.send_transaction(&txn).await?;
-        info!("signature: {:#?}", sig);
-        txs.push(sig.to_string());
+        let tracker = ConfirmationTracker::from_env(aaa);
+        match tracker
+            .send_and_confirm(txn, last_valid_block_height, resign)
+            .await?
+        {
+            TxOutcome::Landed { signature, slot } => {
+                info!("landed: {}, slot: {}", signature, slot);
+                txs.push(signature.to_string());
+            }
+            TxOutcome::Failed { signature, err } => {
+                return Err(anyhow!("{} failed: {:?}", signature, err));
+            }
+            TxOutcome::Expired => return Err(anyhow!("transaction expired before landing")),
+        }
     }
 
     info!("tx ellapsed: {:?}", start_time.elapsed());
@@ -0,0 +1,77 @@
+//! Checks built instructions against the anchor idls in interface/idl.
+
+use std::{collections::HashMap, str::FromStr};
+
+use serde_json::Value;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+pub const PUMP_FUN_IDL: &str = include_str!("../../interface/idl/pump_fun_idl.json");
+pub const PUMP_SWAP_IDL: &str = include_str!("../../interface/idl/pump_swap_idl.json");
+
+/// the program address an idl was generated for
+pub fn idl_address(idl: &str) -> Pubkey {
+    let idl: Value = serde_json::from_str(idl).unwrap();
+    Pubkey::from_str(idl["address"].as_str().unwrap()).unwrap()
+}
+
+/// `instruction` has the discriminator, account order and account flags of
+/// `name` in `idl`. `accounts` maps every idl account name to its pubkey.
+pub fn assert_matches_idl(
+    idl: &str,
+    name: &str,
+    instruction: &Instruction,
+    accounts: &HashMap<&str, Pubkey>,
+) {
+    let idl: Value = serde_json::from_str(idl).unwrap();
+    let idl_instruction = idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|instruction| instruction["name"] == name)
+        .unwrap_or_else(|| panic!("{} not in the idl", name));
+
+    let discriminator: Vec<u8> = idl_instruction["discriminator"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect();
+    assert_eq!(&instruction.data[..8], discriminator.as_slice(), "{}", name);
+
+    let idl_accounts = idl_instruction["accounts"].as_array().unwrap();
+    assert_eq!(instruction.accounts.len(), idl_accounts.len(), "{}", name);
+    for (index, (meta, idl_account)) in instruction.accounts.iter().zip(idl_accounts).enumerate() {
+        let account_name = idl_account["name"].as_str().unwrap();
+        let expected = accounts
+            .get(account_name)
+            .unwrap_or_else(|| panic!("{}: no pubkey for {}", name, account_name));
+        assert_eq!(
+            meta.pubkey, *expected,
+            "{} account {} {}",
+            name, index, account_name
+        );
+        if let Some(address) = idl_account["address"].as_str() {
+            assert_eq!(
+                meta.pubkey.to_string(),
+                address,
+                "{} {}",
+                name,
+                account_name
+            );
+        }
+        assert_eq!(
+            meta.is_writable,
+            idl_account["writable"].as_bool().unwrap_or(false),
+            "{} {} writable",
+            name,
+            account_name
+        );
+        assert_eq!(
+            meta.is_signer,
+            idl_account["signer"].as_bool().unwrap_or(false),
+            "{} {} signer",
+            name,
+            account_name
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use crate::dex::pump_fun::TEN_THOUSAND;
+
+/// `amount * basis_points / 10000` rounded up, the way the pump programs
+/// charge each fee
+pub fn ceil_div_bps(amount: u64, basis_points: u64) -> u64 {
+    (amount as u128 * basis_points as u128).div_ceil(TEN_THOUSAND as u128) as u64
+}
+
+/// Deviation of the execution price from the spot price before the trade,
+/// 0.01 == 1%. Prices are b per a, from the reserves and from the pre-fee
+/// amounts that change hands.
+pub fn price_impact(reserve_a: u128, reserve_b: u128, amount_a: u128, amount_b: u128) -> f64 {
+    if reserve_a == 0 || amount_a == 0 || amount_b == 0 {
+        return 0.0;
+    }
+    let spot = reserve_b as f64 / reserve_a as f64;
+    let execution = amount_b as f64 / amount_a as f64;
+    (execution / spot - 1.0).abs()
+}
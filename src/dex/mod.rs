@@ -1,3 +1,9 @@
-
+pub mod math;
 pub mod pump_fun;
-pub mod raydium;
\ No newline at end of file
+pub mod pump_swap;
+pub mod raydium;
+pub mod traits;
+pub mod venue;
+
+#[cfg(test)]
+pub(crate) mod idl;
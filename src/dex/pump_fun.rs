@@ -1,206 +0,0 @@
-use std::{str::FromStr, sync::Arc};
-
-use anyhow::{anyhow, Context, Result};
-use borsh::from_slice;
-use borsh_derive::{BorshDeserialize, BorshSerialize};
-use log::{error, info};
-// use raydium_amm::math::U128;
-use serde::{Deserialize, Serialize};
-use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
-    signature::Keypair,
-    signer::Signer,
-    system_program,
-};
-use spl_associated_token_account::{
-    get_associated_token_address, instruction::create_associated_token_account,
-};
-use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
-use spl_token_client::token::TokenError;
-
-use crate::{
-    core::{token, tx},
-    engine::swap::{SwapDirection, SwapConfig},
-};
-
-pub const TEN_THOUSAND: u64 = 10000;
-pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
-pub const RENT_PROGRAM: &str = "SysvarRent111111111111111111111111111111111";
-pub const ASSOCIATED_TOKEN_PROGRAM: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
-pub const PUMP_GLOBAL: &str = "4wTV1YmiEkRvAtNtsSGPtUrqRYQMe5SKy2uB4Jjaxnjf";
-pub const PUMP_FEE_RECIPIENT: &str = "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM";
-pub const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
-// pub const PUMP_FUN_MINT_AUTHORITY: &str = "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM";
-pub const PUMP_ACCOUNT: &str = "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1";
-pub const PUMP_BUY_METHOD: u64 = 16927863322537952870;
-pub const PUMP_SELL_METHOD: u64 = 12502976635542562355;
-
-pub struct Pump {
-    pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
-    pub keypair: Arc<Keypair>,
-    pub rpc_client: Option<Arc<solana_client::rpc_client::RpcClient>>,
-}
-
-impl Pump {
-    pub fn new(
-        rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
-        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
-        keypair: Arc<Keypair>,
-    ) -> Self {
-        Self {
-            rpc_nonblocking_client,
-            keypair,
-            rpc_client: Some(rpc_client),
-        }
-    }
-
-    pub async fn swap(&self, mint: &str, swap_config: SwapConfig) -> Result<Vec<String>> {
-        info!("[SWAP IN PUMP.FUN] => ");
-        let slippage_bps = swap_config.slippage * 100;
-        let owner = self.keypair.pubkey();
-        let mint =
-            Pubkey::from_str(mint).map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
-        let program_id = spl_token::ID;
-        let native_mint = spl_token::native_mint::ID;
-
-        let (token_in, token_out, pump_method) = match swap_config.swap_direction {
-            SwapDirection::PC2Coin => (native_mint, mint, PUMP_BUY_METHOD),
-            SwapDirection::Coin2PC => (mint, native_mint, PUMP_SELL_METHOD),
-        };
-        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
-        let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
-            get_bonding_curve_account(self.rpc_client.clone().unwrap(), &mint, &pump_program)
-                .await?;
-
-        let in_ata = token::get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = token::get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
-        );
-
-        let mut create_instruction = None;
-        let mut close_instruction = None;
-
-        tx::new_signed_and_send(
-            &client,
-            &self.keypair,
-            instructions,
-            swap_config.use_jito,
-            &logger,
-        )
-        .await
-    }
-}
-
-fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(TEN_THOUSAND.checked_sub(slippage_bps).unwrap())
-        .unwrap()
-        .checked_div(TEN_THOUSAND)
-        .unwrap()
-}
-fn max_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    input_amount
-        .checked_mul(slippage_bps.checked_add(TEN_THOUSAND).unwrap())
-        .unwrap()
-        .checked_div(TEN_THOUSAND)
-        .unwrap()
-}
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct RaydiumInfo {
-    pub base: f64,
-    pub quote: f64,
-    pub price: f64,
-}
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct PumpInfo {
-    pub mint: String,
-    pub bonding_curve: String,
-    pub associated_bonding_curve: String,
-    pub raydium_pool: Option<String>,
-    pub raydium_info: Option<RaydiumInfo>,
-    pub complete: bool,
-    pub virtual_sol_reserves: u64,
-    pub virtual_token_reserves: u64,
-    pub total_supply: u64,
-}
-
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
-pub struct BondingCurveAccount {
-    pub discriminator: u64,
-    pub virtual_token_reserves: u64,
-    pub virtual_sol_reserves: u64,
-    pub real_token_reserves: u64,
-    pub real_sol_reserves: u64,
-    pub token_total_supply: u64,
-    pub complete: bool,
-}
-
-pub async fn get_bonding_curve_account(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
-    mint: &Pubkey,
-    program_id: &Pubkey,
-) -> Result<(Pubkey, Pubkey, BondingCurveAccount)> {
-    let bonding_curve = get_pda(mint, program_id)?;
-    let associated_bonding_curve = get_associated_token_address(&bonding_curve, mint);
-    let bonding_curve_data = rpc_client
-        .get_account_data(&bonding_curve)
-        .inspect_err(|err| {
-            error!(
-                "Failed to get bonding curve account data: {}, err: {}",
-                bonding_curve, err
-            );
-        })?;
-
-    let bonding_curve_account =
-        from_slice::<BondingCurveAccount>(&bonding_curve_data).map_err(|e| {
-            anyhow!(
-                "Failed to deserialize bonding curve account: {}",
-                e.to_string()
-            )
-        })?;
-
-    Ok((
-        bonding_curve,
-        associated_bonding_curve,
-        bonding_curve_account,
-    ))
-}
-
-pub fn get_pda(mint: &Pubkey, program_id: &Pubkey) -> Result<Pubkey> {
-    let seeds = [b"bonding-curve".as_ref(), mint.as_ref()];
-    let (bonding_curve, _bump) = Pubkey::find_program_address(&seeds, program_id);
-    Ok(bonding_curve)
-}
-
-// https://frontend-api.pump.fun/coins/8zSLdDzM1XsqnfrHmHvA9ir6pvYDjs8UXz6B2Tydd6b2
-pub async fn get_pump_info(
-    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
-    mint: &str,
-) -> Result<PumpInfo> {
-    let mint = Pubkey::from_str(mint)?;
-    let program_id = Pubkey::from_str(PUMP_PROGRAM)?;
-    let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
-        get_bonding_curve_account(rpc_client, &mint, &program_id).await?;
-
-    let pump_info = PumpInfo {
-        mint: mint.to_string(),
-        bonding_curve: bonding_curve.to_string(),
-        associated_bonding_curve: associated_bonding_curve.to_string(),
-        raydium_pool: None,
-        raydium_info: None,
-        complete: bonding_curve_account.complete,
-        virtual_sol_reserves: bonding_curve_account.virtual_sol_reserves,
-        virtual_token_reserves: bonding_curve_account.virtual_token_reserves,
-        total_supply: bonding_curve_account.token_total_supply,
-    };
-    Ok(pump_info)
-}
@@ -0,0 +1,145 @@
+use std::{
+    sync::{Arc, LazyLock, RwLock, atomic::AtomicUsize},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use borsh::BorshDeserialize as _;
+use borsh_derive::{BorshDeserialize, BorshSerialize};
+use log::{error, info};
+use solana_sdk::pubkey::Pubkey;
+
+use super::{BondingCurveAccount, quote::PumpFees};
+use crate::utils::jjj::{import_env_var_with_default, round_robin};
+
+pub const GLOBAL_DISCRIMINATOR: [u8; 8] = [167, 232, 232, 177, 200, 108, 114, 127];
+
+static GLOBAL_CACHE: LazyLock<GlobalCache> = LazyLock::new(|| {
+    GlobalCache::new(Duration::from_secs(import_env_var_with_default(
+        "PUMP_GLOBAL_TTL_SECS",
+        60,
+    )))
+});
+
+/// pump.fun `Global` account, see `types.Global` in interface/idl/pump_fun_idl.json
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct GlobalAccount {
+    pub initialized: bool,
+    pub authority: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub initial_virtual_token_reserves: u64,
+    pub initial_virtual_sol_reserves: u64,
+    pub initial_real_token_reserves: u64,
+    pub token_total_supply: u64,
+    pub fee_basis_points: u64,
+    pub withdraw_authority: Pubkey,
+    pub enable_migrate: bool,
+    pub pool_migration_fee: u64,
+    pub creator_fee_basis_points: u64,
+    pub fee_recipients: [Pubkey; 7],
+    pub set_creator_authority: Pubkey,
+}
+
+impl GlobalAccount {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 || data[..8] != GLOBAL_DISCRIMINATOR {
+            return Err(anyhow!("Invalid pump.fun global account discriminator"));
+        }
+        // the account may be larger than the fields we know about
+        Self::deserialize(&mut &data[8..])
+            .map_err(|e| anyhow!("Failed to deserialize pump.fun global account: {}", e))
+    }
+
+    /// the creator fee is only charged on curves that have a creator set
+    pub fn fees_for(&self, curve: &BondingCurveAccount) -> PumpFees {
+        PumpFees {
+            fee_basis_points: self.fee_basis_points,
+            creator_fee_basis_points: if curve.creator == Pubkey::default() {
+                0
+            } else {
+                self.creator_fee_basis_points
+            },
+        }
+    }
+
+    /// every fee recipient the program currently accepts
+    pub fn valid_fee_recipients(&self) -> Vec<Pubkey> {
+        let mut recipients = Vec::with_capacity(self.fee_recipients.len() + 1);
+        for recipient in std::iter::once(self.fee_recipient).chain(self.fee_recipients) {
+            if recipient != Pubkey::default() && !recipients.contains(&recipient) {
+                recipients.push(recipient);
+            }
+        }
+        recipients
+    }
+
+    pub fn pick_fee_recipient(&self) -> Result<Pubkey> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        round_robin(&NEXT, &self.valid_fee_recipients())
+            .ok_or_else(|| anyhow!("pump.fun global account has no fee recipients"))
+    }
+}
+
+pub fn get_global_pda(program_id: &Pubkey) -> Pubkey {
+    let (global, _bump) = Pubkey::find_program_address(&[b"global"], program_id);
+    global
+}
+
+pub struct GlobalCache {
+    ttl: Duration,
+    inner: RwLock<Option<(Instant, Arc<GlobalAccount>)>>,
+}
+
+impl GlobalCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            inner: RwLock::new(None),
+        }
+    }
+
+    /// cached global account, fetched again once it is older than the ttl
+    pub async fn load(
+        &self,
+        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+        program_id: &Pubkey,
+    ) -> Result<Arc<GlobalAccount>> {
+        if let Some((fetched_at, global)) = self.inner.read().unwrap().as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(global.clone());
+            }
+        }
+        self.refresh(rpc_client, program_id).await
+    }
+
+    pub async fn refresh(
+        &self,
+        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+        program_id: &Pubkey,
+    ) -> Result<Arc<GlobalAccount>> {
+        let global = get_global_pda(program_id);
+        let data = rpc_client.get_account_data(&global).inspect_err(|err| {
+            error!(
+                "Failed to get pump.fun global account data: {}, err: {}",
+                global, err
+            );
+        })?;
+        let global_account = Arc::new(GlobalAccount::decode(&data)?);
+        info!(
+            "pump.fun global refreshed: fee_bps: {}, creator_fee_bps: {}, fee recipients: {}",
+            global_account.fee_basis_points,
+            global_account.creator_fee_basis_points,
+            global_account.valid_fee_recipients().len()
+        );
+        *self.inner.write().unwrap() = Some((Instant::now(), global_account.clone()));
+        Ok(global_account)
+    }
+}
+
+/// process wide cache, ttl set by PUMP_GLOBAL_TTL_SECS
+pub async fn get_global_account(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    program_id: &Pubkey,
+) -> Result<Arc<GlobalAccount>> {
+    GLOBAL_CACHE.load(rpc_client, program_id).await
+}
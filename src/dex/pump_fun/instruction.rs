@@ -0,0 +1,207 @@
+use anyhow::Result;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use super::{PUMP_BUY_METHOD, PUMP_SELL_METHOD, get_pda, global::get_global_pda};
+
+pub fn get_creator_vault_pda(creator: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    let (creator_vault, _bump) =
+        Pubkey::find_program_address(&[b"creator-vault", creator.as_ref()], program_id);
+    creator_vault
+}
+
+pub fn get_event_authority_pda(program_id: &Pubkey) -> Pubkey {
+    let (event_authority, _bump) =
+        Pubkey::find_program_address(&[b"__event_authority"], program_id);
+    event_authority
+}
+
+/// Every account a pump.fun buy/sell touches, derived from the mint, the user,
+/// the chosen fee recipient and the bonding curve creator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PumpFunAccounts {
+    pub program_id: Pubkey,
+    pub global: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub associated_bonding_curve: Pubkey,
+    pub associated_user: Pubkey,
+    pub user: Pubkey,
+    pub token_program: Pubkey,
+    pub creator_vault: Pubkey,
+    pub event_authority: Pubkey,
+}
+
+impl PumpFunAccounts {
+    pub fn new(
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        user: &Pubkey,
+        fee_recipient: &Pubkey,
+        creator: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Self> {
+        let bonding_curve = get_pda(mint, program_id)?;
+        Ok(Self {
+            program_id: *program_id,
+            global: get_global_pda(program_id),
+            fee_recipient: *fee_recipient,
+            mint: *mint,
+            bonding_curve,
+            associated_bonding_curve: get_associated_token_address_with_program_id(
+                &bonding_curve,
+                mint,
+                token_program,
+            ),
+            associated_user: get_associated_token_address_with_program_id(
+                user,
+                mint,
+                token_program,
+            ),
+            user: *user,
+            token_program: *token_program,
+            creator_vault: get_creator_vault_pda(creator, program_id),
+            event_authority: get_event_authority_pda(program_id),
+        })
+    }
+}
+
+/// `buy(amount, max_sol_cost)`, account order follows `instructions.buy` in the idl
+pub fn buy(accounts: &PumpFunAccounts, amount: u64, max_sol_cost: u64) -> Instruction {
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(accounts.global, false),
+            AccountMeta::new(accounts.fee_recipient, false),
+            AccountMeta::new_readonly(accounts.mint, false),
+            AccountMeta::new(accounts.bonding_curve, false),
+            AccountMeta::new(accounts.associated_bonding_curve, false),
+            AccountMeta::new(accounts.associated_user, false),
+            AccountMeta::new(accounts.user, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(accounts.token_program, false),
+            AccountMeta::new(accounts.creator_vault, false),
+            AccountMeta::new_readonly(accounts.event_authority, false),
+            AccountMeta::new_readonly(accounts.program_id, false),
+        ],
+        data: instruction_data(PUMP_BUY_METHOD, amount, max_sol_cost),
+    }
+}
+
+/// `sell(amount, min_sol_output)`, note creator_vault comes before token_program here
+pub fn sell(accounts: &PumpFunAccounts, amount: u64, min_sol_output: u64) -> Instruction {
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(accounts.global, false),
+            AccountMeta::new(accounts.fee_recipient, false),
+            AccountMeta::new_readonly(accounts.mint, false),
+            AccountMeta::new(accounts.bonding_curve, false),
+            AccountMeta::new(accounts.associated_bonding_curve, false),
+            AccountMeta::new(accounts.associated_user, false),
+            AccountMeta::new(accounts.user, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new(accounts.creator_vault, false),
+            AccountMeta::new_readonly(accounts.token_program, false),
+            AccountMeta::new_readonly(accounts.event_authority, false),
+            AccountMeta::new_readonly(accounts.program_id, false),
+        ],
+        data: instruction_data(PUMP_SELL_METHOD, amount, min_sol_output),
+    }
+}
+
+fn instruction_data(method: u64, amount: u64, limit: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&method.to_le_bytes());
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&limit.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use super::*;
+    use crate::dex::{
+        idl::{PUMP_FUN_IDL, assert_matches_idl, idl_address},
+        pump_fun::PUMP_PROGRAM,
+    };
+
+    fn test_accounts() -> PumpFunAccounts {
+        PumpFunAccounts::new(
+            &Pubkey::from_str(PUMP_PROGRAM).unwrap(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &spl_token::ID,
+        )
+        .unwrap()
+    }
+
+    // idl account name -> pubkey
+    fn named(accounts: &PumpFunAccounts) -> HashMap<&'static str, Pubkey> {
+        HashMap::from([
+            ("global", accounts.global),
+            ("fee_recipient", accounts.fee_recipient),
+            ("mint", accounts.mint),
+            ("bonding_curve", accounts.bonding_curve),
+            (
+                "associated_bonding_curve",
+                accounts.associated_bonding_curve,
+            ),
+            ("associated_user", accounts.associated_user),
+            ("user", accounts.user),
+            ("system_program", system_program::ID),
+            ("token_program", accounts.token_program),
+            ("creator_vault", accounts.creator_vault),
+            ("event_authority", accounts.event_authority),
+            ("program", accounts.program_id),
+        ])
+    }
+
+    #[test]
+    fn buy_matches_idl() {
+        let accounts = test_accounts();
+        assert_eq!(accounts.program_id, idl_address(PUMP_FUN_IDL));
+        let instruction = buy(&accounts, 1_000, 2_000);
+        assert_eq!(instruction.program_id, accounts.program_id);
+        assert_matches_idl(PUMP_FUN_IDL, "buy", &instruction, &named(&accounts));
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn sell_matches_idl() {
+        let accounts = test_accounts();
+        let instruction = sell(&accounts, 1_000, 2_000);
+        assert_matches_idl(PUMP_FUN_IDL, "sell", &instruction, &named(&accounts));
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn pdas_follow_idl_seeds() {
+        let accounts = test_accounts();
+        let program_id = accounts.program_id;
+        let (bonding_curve, _) =
+            Pubkey::find_program_address(&[b"bonding-curve", accounts.mint.as_ref()], &program_id);
+        assert_eq!(accounts.bonding_curve, bonding_curve);
+        let (global, _) = Pubkey::find_program_address(&[b"global"], &program_id);
+        assert_eq!(accounts.global, global);
+        assert_eq!(
+            accounts.associated_user,
+            get_associated_token_address_with_program_id(
+                &accounts.user,
+                &accounts.mint,
+                &spl_token::ID
+            )
+        );
+    }
+}
@@ -0,0 +1,350 @@
+pub mod global;
+pub mod instruction;
+pub mod quote;
+
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use borsh::BorshDeserialize as _;
+use borsh_derive::{BorshDeserialize, BorshSerialize};
+use log::{error, info};
+// use raydium_amm::math::U128;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
+
+use crate::{
+    core::{
+        ata::{self, SwapAccounts},
+        token,
+    },
+    dex::traits::Dex,
+    engine::swap::{self, SwapConfig, SwapDirection, SwapInType, SwapQuote},
+};
+use global::GlobalAccount;
+
+pub const TEN_THOUSAND: u64 = 10000;
+pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const RENT_PROGRAM: &str = "SysvarRent111111111111111111111111111111111";
+pub const ASSOCIATED_TOKEN_PROGRAM: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+pub const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+// pub const PUMP_FUN_MINT_AUTHORITY: &str = "TSLvdd1pWpHVjahSpsvCXUbgwsL3JAcvokwaKt1eokM";
+pub const PUMP_ACCOUNT: &str = "Ce6TQqeHC9p8KetsN6JsjHK7UTZk7nasjjnr7XxXp9F1";
+pub const PUMP_BUY_METHOD: u64 = 16927863322537952870;
+pub const PUMP_SELL_METHOD: u64 = 12502976635542562355;
+
+pub struct Pump {
+    pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    pub keypair: Arc<Keypair>,
+    pub rpc_client: Option<Arc<solana_client::rpc_client::RpcClient>>,
+}
+
+impl Pump {
+    pub fn new(
+        rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+        keypair: Arc<Keypair>,
+    ) -> Self {
+        Self {
+            rpc_nonblocking_client,
+            keypair,
+            rpc_client: Some(rpc_client),
+        }
+    }
+
+    pub async fn swap(&self, mint: &str, swap_config: SwapConfig) -> Result<Vec<String>> {
+        info!("[SWAP IN PUMP.FUN] => ");
+        let mint =
+            Pubkey::from_str(mint).map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+        let client = self.rpc_client.clone().unwrap();
+        swap::execute_swap(self, &client, &self.keypair, &mint, &swap_config).await
+    }
+}
+
+pub struct PumpState {
+    pub mint: Pubkey,
+    pub bonding_curve_account: BondingCurveAccount,
+    pub global_account: Arc<GlobalAccount>,
+    pub decimals: u8,
+    pub token_program: Pubkey,
+}
+
+impl Dex for Pump {
+    type State = PumpState;
+
+    async fn load_state(&self, mint: &Pubkey) -> Result<PumpState> {
+        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+        let (_, _, bonding_curve_account) =
+            get_bonding_curve_account(self.rpc_client.clone().unwrap(), mint, &pump_program)
+                .await?;
+        let global_account =
+            global::get_global_account(self.rpc_client.clone().unwrap(), &pump_program).await?;
+        let token_program =
+            token::get_token_program(self.rpc_nonblocking_client.clone(), mint).await?;
+        let mint_info = token::get_mint_info(
+            self.rpc_nonblocking_client.clone(),
+            self.keypair.clone(),
+            mint,
+        )
+        .await?;
+
+        Ok(PumpState {
+            mint: *mint,
+            bonding_curve_account,
+            global_account,
+            decimals: mint_info.base.decimals,
+            token_program,
+        })
+    }
+
+    fn quote(&self, state: &PumpState, swap_config: &SwapConfig) -> Result<SwapQuote> {
+        let slippage_bps = swap_config.slippage * 100;
+        let fees = state.global_account.fees_for(&state.bonding_curve_account);
+        let curve = &state.bonding_curve_account;
+        match (swap_config.swap_direction, swap_config.in_type) {
+            (SwapDirection::PC2Coin, in_type) => {
+                let quote = match in_type {
+                    SwapInType::BaseIn => {
+                        let sol_in = ui_amount_to_amount(
+                            swap_config.amount,
+                            spl_token::native_mint::DECIMALS,
+                        );
+                        quote::quote_buy_exact_sol_in(curve, fees, sol_in)?
+                    }
+                    SwapInType::BaseOut => {
+                        let token_out = ui_amount_to_amount(swap_config.amount, state.decimals);
+                        quote::quote_buy_exact_token_out(curve, fees, token_out)?
+                    }
+                };
+                Ok(SwapQuote {
+                    amount_in: quote.amount_in,
+                    amount_out: quote.amount_out,
+                    transfer_fee: 0,
+                    other_amount_threshold: max_amount_with_slippage(
+                        quote.amount_in,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+            (SwapDirection::Coin2PC, SwapInType::BaseIn) => {
+                let token_in = ui_amount_to_amount(swap_config.amount, state.decimals);
+                let quote = quote::quote_sell_exact_token_in(curve, fees, token_in)?;
+                Ok(SwapQuote {
+                    amount_in: quote.amount_in,
+                    amount_out: quote.amount_out,
+                    transfer_fee: 0,
+                    other_amount_threshold: min_amount_with_slippage(
+                        quote.amount_out,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+            // the program only sells an exact token amount
+            (SwapDirection::Coin2PC, SwapInType::BaseOut) => Err(anyhow!(
+                "QuoteError: pump.fun sells do not support exact sol output"
+            )),
+        }
+    }
+
+    async fn build_swap_instructions(
+        &self,
+        state: &PumpState,
+        swap_config: &SwapConfig,
+    ) -> Result<Vec<Instruction>> {
+        let quote = self.quote(state, swap_config)?;
+        info!(
+            "pump.fun quote: amount_in: {}, amount_out: {}, threshold: {}, price_impact: {:.4}",
+            quote.amount_in, quote.amount_out, quote.other_amount_threshold, quote.price_impact
+        );
+        let owner = self.keypair.pubkey();
+        let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+        // fee recipients rotate, always take one from the live global account
+        let fee_recipient = state.global_account.pick_fee_recipient()?;
+        let accounts = instruction::PumpFunAccounts::new(
+            &pump_program,
+            &state.mint,
+            &owner,
+            &fee_recipient,
+            &state.bonding_curve_account.creator,
+            &state.token_program,
+        )?;
+
+        let mut swap_accounts = SwapAccounts::new();
+        let swap_instruction = match swap_config.swap_direction {
+            SwapDirection::PC2Coin => {
+                swap_accounts.create_ata(&owner, &state.mint, &state.token_program);
+                instruction::buy(&accounts, quote.amount_out, quote.other_amount_threshold)
+            }
+            SwapDirection::Coin2PC => {
+                if swap_config.close_token_account
+                    && ata::is_full_sell(
+                        self.rpc_nonblocking_client.clone(),
+                        self.keypair.clone(),
+                        &state.mint,
+                        &state.token_program,
+                        quote.amount_in,
+                    )
+                    .await?
+                {
+                    swap_accounts.close_ata(&owner, &state.mint, &state.token_program);
+                }
+                instruction::sell(&accounts, quote.amount_in, quote.other_amount_threshold)
+            }
+        };
+        Ok(swap_accounts.wrap(vec![swap_instruction]))
+    }
+
+    fn lookup_table_addresses(&self, state: &PumpState) -> Vec<Pubkey> {
+        let pump_program = Pubkey::from_str(PUMP_PROGRAM).unwrap();
+        let mut addresses = vec![
+            pump_program,
+            global::get_global_pda(&pump_program),
+            instruction::get_event_authority_pda(&pump_program),
+            system_program::ID,
+            state.token_program,
+        ];
+        addresses.extend(state.global_account.valid_fee_recipients());
+        addresses
+    }
+}
+
+pub fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> Result<u64> {
+    let keep_bps = TEN_THOUSAND
+        .checked_sub(slippage_bps)
+        .ok_or_else(|| anyhow!("QuoteError: slippage {} bps above 100%", slippage_bps))?;
+    // at most input_amount, always fits back into u64
+    Ok((input_amount as u128 * keep_bps as u128 / TEN_THOUSAND as u128) as u64)
+}
+pub fn max_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> Result<u64> {
+    let amount =
+        input_amount as u128 * (slippage_bps as u128 + TEN_THOUSAND as u128) / TEN_THOUSAND as u128;
+    u64::try_from(amount).map_err(|_| {
+        anyhow!(
+            "QuoteError: {} with {} bps slippage overflows u64",
+            input_amount,
+            slippage_bps
+        )
+    })
+}
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaydiumInfo {
+    pub base: f64,
+    pub quote: f64,
+    pub price: f64,
+}
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PumpInfo {
+    pub mint: String,
+    pub bonding_curve: String,
+    pub associated_bonding_curve: String,
+    pub raydium_pool: Option<String>,
+    pub raydium_info: Option<RaydiumInfo>,
+    pub complete: bool,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+    pub total_supply: u64,
+}
+
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize)]
+pub struct BondingCurveAccount {
+    pub discriminator: u64,
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub token_total_supply: u64,
+    pub complete: bool,
+    pub creator: Pubkey,
+}
+
+pub async fn get_bonding_curve_account(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<(Pubkey, Pubkey, BondingCurveAccount)> {
+    let bonding_curve = get_pda(mint, program_id)?;
+    // token-2022 mints keep the curve's tokens under the token-2022 ata
+    let token_program = rpc_client.get_account(mint)?.owner;
+    if !token::is_token_program(&token_program) {
+        return Err(anyhow!("{} is not a token mint", mint));
+    }
+    let associated_bonding_curve =
+        get_associated_token_address_with_program_id(&bonding_curve, mint, &token_program);
+    let bonding_curve_data = rpc_client
+        .get_account_data(&bonding_curve)
+        .inspect_err(|err| {
+            error!(
+                "Failed to get bonding curve account data: {}, err: {}",
+                bonding_curve, err
+            );
+        })?;
+
+    // newer curves carry extra trailing fields, only read the ones we know about
+    let bonding_curve_account =
+        BondingCurveAccount::deserialize(&mut bonding_curve_data.as_slice()).map_err(|e| {
+            anyhow!(
+                "Failed to deserialize bonding curve account: {}",
+                e.to_string()
+            )
+        })?;
+
+    Ok((
+        bonding_curve,
+        associated_bonding_curve,
+        bonding_curve_account,
+    ))
+}
+
+pub fn get_pda(mint: &Pubkey, program_id: &Pubkey) -> Result<Pubkey> {
+    let seeds = [b"bonding-curve".as_ref(), mint.as_ref()];
+    let (bonding_curve, _bump) = Pubkey::find_program_address(&seeds, program_id);
+    Ok(bonding_curve)
+}
+
+// https://frontend-api.pump.fun/coins/8zSLdDzM1XsqnfrHmHvA9ir6pvYDjs8UXz6B2Tydd6b2
+pub async fn get_pump_info(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<PumpInfo> {
+    let mint = Pubkey::from_str(mint)?;
+    let program_id = Pubkey::from_str(PUMP_PROGRAM)?;
+    let (bonding_curve, associated_bonding_curve, bonding_curve_account) =
+        get_bonding_curve_account(rpc_client, &mint, &program_id).await?;
+
+    let pump_info = PumpInfo {
+        mint: mint.to_string(),
+        bonding_curve: bonding_curve.to_string(),
+        associated_bonding_curve: associated_bonding_curve.to_string(),
+        raydium_pool: None,
+        raydium_info: None,
+        complete: bonding_curve_account.complete,
+        virtual_sol_reserves: bonding_curve_account.virtual_sol_reserves,
+        virtual_token_reserves: bonding_curve_account.virtual_token_reserves,
+        total_supply: bonding_curve_account.token_total_supply,
+    };
+    Ok(pump_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slippage_bounds() {
+        assert_eq!(min_amount_with_slippage(10_000, 100).unwrap(), 9_900);
+        assert_eq!(max_amount_with_slippage(10_000, 100).unwrap(), 10_100);
+        // u64::MAX * 10100 overflows u64 but not u128
+        assert_eq!(min_amount_with_slippage(u64::MAX, 0).unwrap(), u64::MAX);
+        assert!(max_amount_with_slippage(u64::MAX, 100).is_err());
+        assert!(min_amount_with_slippage(1, TEN_THOUSAND + 1).is_err());
+    }
+}
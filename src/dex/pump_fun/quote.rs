@@ -0,0 +1,279 @@
+use anyhow::{Result, anyhow};
+
+use super::{BondingCurveAccount, TEN_THOUSAND};
+use crate::dex::math::{ceil_div_bps, price_impact};
+
+/// Fee settings taken from the pump.fun Global account
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PumpFees {
+    pub fee_basis_points: u64,
+    pub creator_fee_basis_points: u64,
+}
+
+impl PumpFees {
+    pub fn total_basis_points(&self) -> u64 {
+        self.fee_basis_points + self.creator_fee_basis_points
+    }
+
+    // the program rounds each fee up on its own, so the sum can be 1 lamport
+    // above a single ceil over total_basis_points
+    pub fn compute_fee(&self, amount: u64) -> u64 {
+        ceil_div_bps(amount, self.fee_basis_points)
+            + ceil_div_bps(amount, self.creator_fee_basis_points)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CurveReserves {
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub real_sol_reserves: u64,
+}
+
+impl From<&BondingCurveAccount> for CurveReserves {
+    fn from(curve: &BondingCurveAccount) -> Self {
+        Self {
+            virtual_token_reserves: curve.virtual_token_reserves,
+            virtual_sol_reserves: curve.virtual_sol_reserves,
+            real_token_reserves: curve.real_token_reserves,
+            real_sol_reserves: curve.real_sol_reserves,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PumpQuote {
+    /// lamports paid including fees (buy), or tokens sold (sell)
+    pub amount_in: u64,
+    /// tokens received (buy), or lamports received after fees (sell)
+    pub amount_out: u64,
+    /// lamports charged as protocol + creator fee
+    pub fee: u64,
+    pub price_impact: f64,
+    pub reserves_after: CurveReserves,
+}
+
+/// Tokens received for spending at most `sol_in` lamports, fees included.
+/// `amount_in` is what the program will actually charge for `amount_out`.
+pub fn quote_buy_exact_sol_in(
+    curve: &BondingCurveAccount,
+    fees: PumpFees,
+    sol_in: u64,
+) -> Result<PumpQuote> {
+    check_curve(curve)?;
+    if sol_in <= 1 {
+        return Err(anyhow!("QuoteError: sol amount too small: {}", sol_in));
+    }
+    // strip the fee from the budget before running it through the curve
+    let input_amount = (sol_in as u128 - 1) * TEN_THOUSAND as u128
+        / (fees.total_basis_points() + TEN_THOUSAND) as u128;
+    let tokens = input_amount * curve.virtual_token_reserves as u128
+        / (curve.virtual_sol_reserves as u128 + input_amount);
+    let tokens = (tokens as u64).min(curve.real_token_reserves);
+    if tokens == 0 {
+        return Err(anyhow!(
+            "QuoteError: sol amount {} buys zero tokens",
+            sol_in
+        ));
+    }
+
+    quote_buy_exact_token_out(curve, fees, tokens)
+}
+
+/// Lamports needed to buy exactly `token_out` tokens, fees included.
+/// This is the value the program checks against `max_sol_cost`.
+pub fn quote_buy_exact_token_out(
+    curve: &BondingCurveAccount,
+    fees: PumpFees,
+    token_out: u64,
+) -> Result<PumpQuote> {
+    check_curve(curve)?;
+    if token_out > curve.real_token_reserves {
+        return Err(anyhow!(
+            "QuoteError: token amount {} exceeds real reserves {}",
+            token_out,
+            curve.real_token_reserves
+        ));
+    }
+    if token_out == 0 {
+        return Err(anyhow!("QuoteError: token amount is zero"));
+    }
+    if token_out >= curve.virtual_token_reserves {
+        return Err(anyhow!(
+            "QuoteError: token amount {} exceeds virtual reserves {}",
+            token_out,
+            curve.virtual_token_reserves
+        ));
+    }
+
+    let sol_cost = token_out as u128 * curve.virtual_sol_reserves as u128
+        / (curve.virtual_token_reserves - token_out) as u128
+        + 1;
+    let sol_cost = u64::try_from(sol_cost)?;
+    let fee = fees.compute_fee(sol_cost);
+
+    let mut reserves_after = CurveReserves::from(curve);
+    reserves_after.virtual_token_reserves -= token_out;
+    reserves_after.real_token_reserves -= token_out;
+    reserves_after.virtual_sol_reserves += sol_cost;
+    reserves_after.real_sol_reserves += sol_cost;
+
+    Ok(PumpQuote {
+        amount_in: sol_cost + fee,
+        amount_out: token_out,
+        fee,
+        price_impact: curve_price_impact(curve, sol_cost, token_out),
+        reserves_after,
+    })
+}
+
+/// Lamports received for selling exactly `token_in` tokens, fees deducted.
+/// This is the value the program checks against `min_sol_output`.
+pub fn quote_sell_exact_token_in(
+    curve: &BondingCurveAccount,
+    fees: PumpFees,
+    token_in: u64,
+) -> Result<PumpQuote> {
+    check_curve(curve)?;
+    if token_in == 0 {
+        return Err(anyhow!("QuoteError: token amount is zero"));
+    }
+
+    let sol_out = token_in as u128 * curve.virtual_sol_reserves as u128
+        / (curve.virtual_token_reserves as u128 + token_in as u128);
+    let sol_out = u64::try_from(sol_out)?;
+    if sol_out > curve.real_sol_reserves {
+        return Err(anyhow!(
+            "QuoteError: sol output {} exceeds real reserves {}",
+            sol_out,
+            curve.real_sol_reserves
+        ));
+    }
+    let fee = fees.compute_fee(sol_out);
+
+    let mut reserves_after = CurveReserves::from(curve);
+    reserves_after.virtual_token_reserves += token_in;
+    reserves_after.real_token_reserves += token_in;
+    reserves_after.virtual_sol_reserves -= sol_out;
+    reserves_after.real_sol_reserves -= sol_out;
+
+    Ok(PumpQuote {
+        amount_in: token_in,
+        amount_out: sol_out.saturating_sub(fee),
+        fee,
+        price_impact: curve_price_impact(curve, sol_out, token_in),
+        reserves_after,
+    })
+}
+
+fn check_curve(curve: &BondingCurveAccount) -> Result<()> {
+    if curve.complete {
+        return Err(anyhow!("QuoteError: bonding curve is complete"));
+    }
+    if curve.virtual_token_reserves == 0 || curve.virtual_sol_reserves == 0 {
+        return Err(anyhow!("QuoteError: bonding curve has empty reserves"));
+    }
+    Ok(())
+}
+
+fn curve_price_impact(curve: &BondingCurveAccount, sol_amount: u64, token_amount: u64) -> f64 {
+    price_impact(
+        curve.virtual_token_reserves as u128,
+        curve.virtual_sol_reserves as u128,
+        token_amount as u128,
+        sol_amount as u128,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEES: PumpFees = PumpFees {
+        fee_basis_points: 95,
+        creator_fee_basis_points: 5,
+    };
+
+    // a freshly launched curve
+    fn new_curve() -> BondingCurveAccount {
+        BondingCurveAccount {
+            virtual_token_reserves: 1_073_000_000_000_000,
+            virtual_sol_reserves: 30_000_000_000,
+            real_token_reserves: 793_100_000_000_000,
+            real_sol_reserves: 0,
+            token_total_supply: 1_000_000_000_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fees_round_up_per_component() {
+        // 0.95 and 0.05 lamports both round up, one ceil over 100 bps gives 1
+        assert_eq!(FEES.compute_fee(100), 2);
+        assert_eq!(FEES.compute_fee(1001), 11);
+        assert_eq!(FEES.compute_fee(0), 0);
+    }
+
+    #[test]
+    fn buy_exact_sol_in() {
+        let quote = quote_buy_exact_sol_in(&new_curve(), FEES, 1_000_000_000).unwrap();
+        // (1_000_000_000 - 1) * 10000 / 10100 = 990_099_008 through the curve
+        assert_eq!(quote.amount_out, 34_281_150_096_027);
+        assert_eq!(quote.fee, 9_900_991);
+        assert_eq!(quote.amount_in, 999_999_999);
+        assert!(quote.amount_in <= 1_000_000_000);
+        assert_eq!(
+            quote.reserves_after.virtual_token_reserves,
+            1_038_718_849_903_973
+        );
+        assert_eq!(quote.reserves_after.virtual_sol_reserves, 30_990_099_008);
+        assert_eq!(quote.reserves_after.real_sol_reserves, 990_099_008);
+    }
+
+    #[test]
+    fn buy_exact_token_out() {
+        let quote = quote_buy_exact_token_out(&new_curve(), FEES, 34_281_150_096_027).unwrap();
+        // token_out * virtual_sol / (virtual_token - token_out) + 1
+        assert_eq!(quote.amount_in - quote.fee, 990_099_008);
+        assert_eq!(quote.fee, 9_900_991);
+    }
+
+    #[test]
+    fn buy_beyond_real_reserves_fails() {
+        let curve = new_curve();
+        assert!(quote_buy_exact_token_out(&curve, FEES, curve.real_token_reserves + 1).is_err());
+        assert!(quote_buy_exact_token_out(&curve, FEES, 0).is_err());
+        assert!(quote_buy_exact_sol_in(&curve, FEES, 1).is_err());
+    }
+
+    #[test]
+    fn sell_exact_token_in() {
+        let curve = BondingCurveAccount {
+            virtual_token_reserves: 1_038_718_849_903_973,
+            virtual_sol_reserves: 30_990_099_008,
+            real_token_reserves: 758_818_849_903_973,
+            real_sol_reserves: 990_099_008,
+            ..new_curve()
+        };
+        let quote = quote_sell_exact_token_in(&curve, FEES, 34_281_150_096_027).unwrap();
+        // floor(token_in * virtual_sol / (virtual_token + token_in)) = 990_099_007
+        assert_eq!(quote.fee, 9_900_991);
+        assert_eq!(quote.amount_out, 980_198_016);
+        assert_eq!(quote.reserves_after.real_sol_reserves, 1);
+    }
+
+    #[test]
+    fn sell_beyond_real_sol_fails() {
+        assert!(quote_sell_exact_token_in(&new_curve(), FEES, 1_000_000_000).is_err());
+    }
+
+    #[test]
+    fn complete_curve_fails() {
+        let curve = BondingCurveAccount {
+            complete: true,
+            ..new_curve()
+        };
+        assert!(quote_buy_exact_sol_in(&curve, FEES, 1_000_000_000).is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use super::{Pool, get_coin_creator_vault_authority_pda, get_global_config_pda};
+use crate::dex::pump_fun::instruction::get_event_authority_pda;
+
+pub const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+pub const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+/// All 19 accounts of a PumpSwap buy/sell, in the order of `instructions.buy`
+/// in interface/idl/pump_swap_idl.json (sell uses the same list).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PumpSwapAccounts {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub global_config: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub protocol_fee_recipient: Pubkey,
+    pub protocol_fee_recipient_token_account: Pubkey,
+    pub base_token_program: Pubkey,
+    pub quote_token_program: Pubkey,
+    pub event_authority: Pubkey,
+    pub program_id: Pubkey,
+    pub coin_creator_vault_ata: Pubkey,
+    pub coin_creator_vault_authority: Pubkey,
+}
+
+impl PumpSwapAccounts {
+    pub fn new(
+        program_id: &Pubkey,
+        pool_id: &Pubkey,
+        pool: &Pool,
+        user: &Pubkey,
+        protocol_fee_recipient: &Pubkey,
+        base_token_program: &Pubkey,
+        quote_token_program: &Pubkey,
+    ) -> Self {
+        let coin_creator_vault_authority =
+            get_coin_creator_vault_authority_pda(&pool.coin_creator, program_id);
+        Self {
+            pool: *pool_id,
+            user: *user,
+            global_config: get_global_config_pda(program_id),
+            base_mint: pool.base_mint,
+            quote_mint: pool.quote_mint,
+            user_base_token_account: get_associated_token_address_with_program_id(
+                user,
+                &pool.base_mint,
+                base_token_program,
+            ),
+            user_quote_token_account: get_associated_token_address_with_program_id(
+                user,
+                &pool.quote_mint,
+                quote_token_program,
+            ),
+            pool_base_token_account: pool.pool_base_token_account,
+            pool_quote_token_account: pool.pool_quote_token_account,
+            protocol_fee_recipient: *protocol_fee_recipient,
+            protocol_fee_recipient_token_account: get_associated_token_address_with_program_id(
+                protocol_fee_recipient,
+                &pool.quote_mint,
+                quote_token_program,
+            ),
+            base_token_program: *base_token_program,
+            quote_token_program: *quote_token_program,
+            event_authority: get_event_authority_pda(program_id),
+            program_id: *program_id,
+            coin_creator_vault_ata: get_associated_token_address_with_program_id(
+                &coin_creator_vault_authority,
+                &pool.quote_mint,
+                quote_token_program,
+            ),
+            coin_creator_vault_authority,
+        }
+    }
+
+    fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.pool, false),
+            AccountMeta::new(self.user, true),
+            AccountMeta::new_readonly(self.global_config, false),
+            AccountMeta::new_readonly(self.base_mint, false),
+            AccountMeta::new_readonly(self.quote_mint, false),
+            AccountMeta::new(self.user_base_token_account, false),
+            AccountMeta::new(self.user_quote_token_account, false),
+            AccountMeta::new(self.pool_base_token_account, false),
+            AccountMeta::new(self.pool_quote_token_account, false),
+            AccountMeta::new_readonly(self.protocol_fee_recipient, false),
+            AccountMeta::new(self.protocol_fee_recipient_token_account, false),
+            AccountMeta::new_readonly(self.base_token_program, false),
+            AccountMeta::new_readonly(self.quote_token_program, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+            AccountMeta::new_readonly(self.event_authority, false),
+            AccountMeta::new_readonly(self.program_id, false),
+            AccountMeta::new(self.coin_creator_vault_ata, false),
+            AccountMeta::new_readonly(self.coin_creator_vault_authority, false),
+        ]
+    }
+}
+
+/// `buy(base_amount_out, max_quote_amount_in)`
+pub fn buy(
+    accounts: &PumpSwapAccounts,
+    base_amount_out: u64,
+    max_quote_amount_in: u64,
+) -> Instruction {
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: accounts.to_account_metas(),
+        data: instruction_data(BUY_DISCRIMINATOR, base_amount_out, max_quote_amount_in),
+    }
+}
+
+/// `sell(base_amount_in, min_quote_amount_out)`
+pub fn sell(
+    accounts: &PumpSwapAccounts,
+    base_amount_in: u64,
+    min_quote_amount_out: u64,
+) -> Instruction {
+    Instruction {
+        program_id: accounts.program_id,
+        accounts: accounts.to_account_metas(),
+        data: instruction_data(SELL_DISCRIMINATOR, base_amount_in, min_quote_amount_out),
+    }
+}
+
+fn instruction_data(discriminator: [u8; 8], amount: u64, limit: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(24);
+    data.extend_from_slice(&discriminator);
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&limit.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use super::*;
+    use crate::dex::{
+        idl::{PUMP_SWAP_IDL, assert_matches_idl, idl_address},
+        pump_swap::PUMP_SWAP_PROGRAM,
+    };
+
+    fn test_accounts() -> PumpSwapAccounts {
+        let pool = Pool {
+            pool_bump: 255,
+            index: 0,
+            creator: Pubkey::new_unique(),
+            base_mint: Pubkey::new_unique(),
+            quote_mint: spl_token::native_mint::ID,
+            lp_mint: Pubkey::new_unique(),
+            pool_base_token_account: Pubkey::new_unique(),
+            pool_quote_token_account: Pubkey::new_unique(),
+            lp_supply: 0,
+            coin_creator: Pubkey::new_unique(),
+        };
+        PumpSwapAccounts::new(
+            &Pubkey::from_str(PUMP_SWAP_PROGRAM).unwrap(),
+            &Pubkey::new_unique(),
+            &pool,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &spl_token_2022::ID,
+            &spl_token::ID,
+        )
+    }
+
+    // idl account name -> pubkey
+    fn named(accounts: &PumpSwapAccounts) -> HashMap<&'static str, Pubkey> {
+        HashMap::from([
+            ("pool", accounts.pool),
+            ("user", accounts.user),
+            ("global_config", accounts.global_config),
+            ("base_mint", accounts.base_mint),
+            ("quote_mint", accounts.quote_mint),
+            ("user_base_token_account", accounts.user_base_token_account),
+            (
+                "user_quote_token_account",
+                accounts.user_quote_token_account,
+            ),
+            ("pool_base_token_account", accounts.pool_base_token_account),
+            (
+                "pool_quote_token_account",
+                accounts.pool_quote_token_account,
+            ),
+            ("protocol_fee_recipient", accounts.protocol_fee_recipient),
+            (
+                "protocol_fee_recipient_token_account",
+                accounts.protocol_fee_recipient_token_account,
+            ),
+            ("base_token_program", accounts.base_token_program),
+            ("quote_token_program", accounts.quote_token_program),
+            ("system_program", system_program::ID),
+            ("associated_token_program", spl_associated_token_account::ID),
+            ("event_authority", accounts.event_authority),
+            ("program", accounts.program_id),
+            ("coin_creator_vault_ata", accounts.coin_creator_vault_ata),
+            (
+                "coin_creator_vault_authority",
+                accounts.coin_creator_vault_authority,
+            ),
+        ])
+    }
+
+    #[test]
+    fn buy_matches_idl() {
+        let accounts = test_accounts();
+        assert_eq!(accounts.program_id, idl_address(PUMP_SWAP_IDL));
+        let instruction = buy(&accounts, 1_000, 2_000);
+        assert_eq!(instruction.accounts.len(), 19);
+        assert_matches_idl(PUMP_SWAP_IDL, "buy", &instruction, &named(&accounts));
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn sell_matches_idl() {
+        let accounts = test_accounts();
+        let instruction = sell(&accounts, 1_000, 2_000);
+        assert_matches_idl(PUMP_SWAP_IDL, "sell", &instruction, &named(&accounts));
+        assert_eq!(&instruction.data[8..16], &1_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[16..], &2_000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn token_accounts_follow_their_token_program() {
+        let accounts = test_accounts();
+        assert_eq!(
+            accounts.user_base_token_account,
+            get_associated_token_address_with_program_id(
+                &accounts.user,
+                &accounts.base_mint,
+                &spl_token_2022::ID
+            )
+        );
+        assert_eq!(
+            accounts.user_quote_token_account,
+            get_associated_token_address_with_program_id(
+                &accounts.user,
+                &accounts.quote_mint,
+                &spl_token::ID
+            )
+        );
+    }
+}
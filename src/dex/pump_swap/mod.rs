@@ -0,0 +1,628 @@
+pub mod instruction;
+pub mod quote;
+
+use std::{
+    str::FromStr,
+    sync::{Arc, atomic::AtomicUsize},
+};
+
+use anyhow::{Result, anyhow};
+use borsh::BorshDeserialize as _;
+use borsh_derive::{BorshDeserialize, BorshSerialize};
+use log::{error, info};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token::ui_amount_to_amount;
+use spl_token_2022::{
+    extension::{StateWithExtensions, StateWithExtensionsOwned},
+    state::{Account, Mint},
+};
+
+use crate::{
+    core::{
+        ata::{self, SwapAccounts},
+        token,
+    },
+    dex::{
+        pump_fun::{
+            PUMP_PROGRAM, instruction::get_event_authority_pda, max_amount_with_slippage,
+            min_amount_with_slippage,
+        },
+        traits::Dex,
+    },
+    engine::swap::{self, SwapConfig, SwapDirection, SwapInType, SwapQuote},
+    utils::jjj::round_robin,
+};
+use quote::PumpSwapFees;
+
+pub const PUMP_SWAP_PROGRAM: &str = "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA";
+pub const POOL_DISCRIMINATOR: [u8; 8] = [241, 154, 109, 4, 17, 177, 109, 188];
+pub const GLOBAL_CONFIG_DISCRIMINATOR: [u8; 8] = [149, 8, 156, 202, 160, 252, 176, 217];
+/// pools created by the bonding curve migration always use index 0
+pub const CANONICAL_POOL_INDEX: u16 = 0;
+
+pub struct PumpSwap {
+    pub rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    pub keypair: Arc<Keypair>,
+    pub rpc_client: Option<Arc<solana_client::rpc_client::RpcClient>>,
+}
+
+impl PumpSwap {
+    pub fn new(
+        rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+        rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+        keypair: Arc<Keypair>,
+    ) -> Self {
+        Self {
+            rpc_nonblocking_client,
+            keypair,
+            rpc_client: Some(rpc_client),
+        }
+    }
+
+    pub async fn swap(&self, mint: &str, swap_config: SwapConfig) -> Result<Vec<String>> {
+        info!("[SWAP IN PUMP SWAP] => ");
+        let mint =
+            Pubkey::from_str(mint).map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+        let client = self.rpc_client.clone().unwrap();
+        swap::execute_swap(self, &client, &self.keypair, &mint, &swap_config).await
+    }
+}
+
+pub struct PumpSwapState {
+    pub pool_id: Pubkey,
+    pub pool: Pool,
+    pub global_config: GlobalConfig,
+    pub base_reserve: u64,
+    pub quote_reserve: u64,
+    pub base_mint: StateWithExtensionsOwned<Mint>,
+    pub base_token_program: Pubkey,
+    pub quote_token_program: Pubkey,
+    /// only fetched when the base mint has a transfer fee
+    pub epoch: u64,
+}
+
+impl Dex for PumpSwap {
+    type State = PumpSwapState;
+
+    async fn load_state(&self, mint: &Pubkey) -> Result<PumpSwapState> {
+        let client = self.rpc_client.clone().unwrap();
+        let pool_id = get_canonical_pool_pda(mint)?;
+        let pool = get_pool(client.clone(), &pool_id).await?;
+        let global_config = get_global_config(client.clone()).await?;
+        let (base_reserve, quote_reserve) = get_pool_reserves(client, &pool).await?;
+        let base_token_program =
+            token::get_token_program(self.rpc_nonblocking_client.clone(), &pool.base_mint).await?;
+        let quote_token_program =
+            token::get_token_program(self.rpc_nonblocking_client.clone(), &pool.quote_mint).await?;
+        let base_mint = token::get_mint_info(
+            self.rpc_nonblocking_client.clone(),
+            self.keypair.clone(),
+            &pool.base_mint,
+        )
+        .await?;
+        let epoch = if token::has_transfer_fee(&base_mint) {
+            self.rpc_nonblocking_client.get_epoch_info().await?.epoch
+        } else {
+            0
+        };
+
+        Ok(PumpSwapState {
+            pool_id,
+            pool,
+            global_config,
+            base_reserve,
+            quote_reserve,
+            base_mint,
+            base_token_program,
+            quote_token_program,
+            epoch,
+        })
+    }
+
+    fn quote(&self, state: &PumpSwapState, swap_config: &SwapConfig) -> Result<SwapQuote> {
+        if state.pool.quote_mint != spl_token::native_mint::ID {
+            return Err(anyhow!("QuoteError: pump swap pool is not quoted in wsol"));
+        }
+        let slippage_bps = swap_config.slippage * 100;
+        let fees = state.global_config.fees_for(&state.pool);
+        let (base_reserve, quote_reserve) = (state.base_reserve, state.quote_reserve);
+        let decimals = state.base_mint.base.decimals;
+        match (swap_config.swap_direction, swap_config.in_type) {
+            (SwapDirection::PC2Coin, in_type) => {
+                if state.global_config.buy_disabled() {
+                    return Err(anyhow!("QuoteError: pump swap buys are disabled"));
+                }
+                let quote = match in_type {
+                    SwapInType::BaseIn => {
+                        let quote_in = ui_amount_to_amount(
+                            swap_config.amount,
+                            spl_token::native_mint::DECIMALS,
+                        );
+                        quote::quote_buy_exact_quote_in(
+                            base_reserve,
+                            quote_reserve,
+                            fees,
+                            quote_in,
+                        )?
+                    }
+                    SwapInType::BaseOut => {
+                        // gross up so the wanted amount is left after the transfer fee
+                        let base_out = ui_amount_to_amount(swap_config.amount, decimals);
+                        let base_out = base_out
+                            + token::get_inverse_transfer_fee(
+                                &state.base_mint,
+                                state.epoch,
+                                base_out,
+                            );
+                        quote::quote_buy_exact_base_out(
+                            base_reserve,
+                            quote_reserve,
+                            fees,
+                            base_out,
+                        )?
+                    }
+                };
+                let transfer_fee =
+                    token::get_transfer_fee(&state.base_mint, state.epoch, quote.amount_out);
+                Ok(SwapQuote {
+                    amount_in: quote.amount_in,
+                    amount_out: quote.amount_out - transfer_fee,
+                    transfer_fee,
+                    other_amount_threshold: max_amount_with_slippage(
+                        quote.amount_in,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+            (SwapDirection::Coin2PC, SwapInType::BaseIn) => {
+                if state.global_config.sell_disabled() {
+                    return Err(anyhow!("QuoteError: pump swap sells are disabled"));
+                }
+                // the pool only receives what is left after the transfer fee
+                let base_in = ui_amount_to_amount(swap_config.amount, decimals);
+                let transfer_fee = token::get_transfer_fee(&state.base_mint, state.epoch, base_in);
+                let quote = quote::quote_sell_exact_base_in(
+                    base_reserve,
+                    quote_reserve,
+                    fees,
+                    base_in - transfer_fee,
+                )?;
+                Ok(SwapQuote {
+                    amount_in: base_in,
+                    amount_out: quote.amount_out,
+                    transfer_fee,
+                    other_amount_threshold: min_amount_with_slippage(
+                        quote.amount_out,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+            // the program only sells an exact base amount
+            (SwapDirection::Coin2PC, SwapInType::BaseOut) => Err(anyhow!(
+                "QuoteError: pump swap sells do not support exact quote output"
+            )),
+        }
+    }
+
+    async fn build_swap_instructions(
+        &self,
+        state: &PumpSwapState,
+        swap_config: &SwapConfig,
+    ) -> Result<Vec<Instruction>> {
+        let quote = self.quote(state, swap_config)?;
+        info!(
+            "pump swap quote: amount_in: {}, amount_out: {}, transfer_fee: {}, threshold: {}, price_impact: {:.4}",
+            quote.amount_in,
+            quote.amount_out,
+            quote.transfer_fee,
+            quote.other_amount_threshold,
+            quote.price_impact
+        );
+        let owner = self.keypair.pubkey();
+        let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+        let protocol_fee_recipient = state.global_config.pick_protocol_fee_recipient()?;
+        let accounts = instruction::PumpSwapAccounts::new(
+            &program_id,
+            &state.pool_id,
+            &state.pool,
+            &owner,
+            &protocol_fee_recipient,
+            &state.base_token_program,
+            &state.quote_token_program,
+        );
+
+        let mut swap_accounts = SwapAccounts::new();
+        let swap_instruction = match swap_config.swap_direction {
+            SwapDirection::PC2Coin => {
+                // wrap the most the buy may spend, the rest is unwrapped on close
+                swap_accounts.wrap_sol(&owner, quote.other_amount_threshold);
+                swap_accounts.create_ata(&owner, &state.pool.base_mint, &state.base_token_program);
+                // base_amount_out is what leaves the pool, before the transfer fee
+                instruction::buy(
+                    &accounts,
+                    quote.amount_out + quote.transfer_fee,
+                    quote.other_amount_threshold,
+                )
+            }
+            SwapDirection::Coin2PC => {
+                swap_accounts.receive_sol(&owner);
+                if swap_config.close_token_account
+                    && ata::is_full_sell(
+                        self.rpc_nonblocking_client.clone(),
+                        self.keypair.clone(),
+                        &state.pool.base_mint,
+                        &state.base_token_program,
+                        quote.amount_in,
+                    )
+                    .await?
+                {
+                    swap_accounts.close_ata(
+                        &owner,
+                        &state.pool.base_mint,
+                        &state.base_token_program,
+                    );
+                }
+                instruction::sell(&accounts, quote.amount_in, quote.other_amount_threshold)
+            }
+        };
+        Ok(swap_accounts.wrap(vec![swap_instruction]))
+    }
+
+    fn lookup_table_addresses(&self, state: &PumpSwapState) -> Vec<Pubkey> {
+        let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM).unwrap();
+        let coin_creator_vault_authority =
+            get_coin_creator_vault_authority_pda(&state.pool.coin_creator, &program_id);
+        let mut addresses = vec![
+            program_id,
+            get_global_config_pda(&program_id),
+            get_event_authority_pda(&program_id),
+            solana_sdk::system_program::ID,
+            spl_associated_token_account::ID,
+            state.base_token_program,
+            state.quote_token_program,
+            state.pool_id,
+            state.pool.base_mint,
+            state.pool.quote_mint,
+            state.pool.pool_base_token_account,
+            state.pool.pool_quote_token_account,
+            coin_creator_vault_authority,
+            get_associated_token_address_with_program_id(
+                &coin_creator_vault_authority,
+                &state.pool.quote_mint,
+                &state.quote_token_program,
+            ),
+        ];
+        // every recipient the protocol fee may rotate to, with its quote ata
+        for recipient in state.global_config.protocol_fee_recipients {
+            if recipient != Pubkey::default() {
+                addresses.push(recipient);
+                addresses.push(get_associated_token_address_with_program_id(
+                    &recipient,
+                    &state.pool.quote_mint,
+                    &state.quote_token_program,
+                ));
+            }
+        }
+        addresses
+    }
+}
+
+/// PumpSwap `Pool` account, see `types.Pool` in interface/idl/pump_swap_idl.json
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct Pool {
+    pub pool_bump: u8,
+    pub index: u16,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub pool_base_token_account: Pubkey,
+    pub pool_quote_token_account: Pubkey,
+    pub lp_supply: u64,
+    pub coin_creator: Pubkey,
+}
+
+impl Pool {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 || data[..8] != POOL_DISCRIMINATOR {
+            return Err(anyhow!("Invalid pump swap pool discriminator"));
+        }
+        Self::deserialize(&mut &data[8..])
+            .map_err(|e| anyhow!("Failed to deserialize pump swap pool: {}", e))
+    }
+}
+
+/// PumpSwap `GlobalConfig` account
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    pub lp_fee_basis_points: u64,
+    pub protocol_fee_basis_points: u64,
+    /// bit 3 - disable buy, bit 4 - disable sell
+    pub disable_flags: u8,
+    pub protocol_fee_recipients: [Pubkey; 8],
+    pub coin_creator_fee_basis_points: u64,
+}
+
+impl GlobalConfig {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < 8 || data[..8] != GLOBAL_CONFIG_DISCRIMINATOR {
+            return Err(anyhow!("Invalid pump swap global config discriminator"));
+        }
+        Self::deserialize(&mut &data[8..])
+            .map_err(|e| anyhow!("Failed to deserialize pump swap global config: {}", e))
+    }
+
+    /// the coin creator fee is only charged on pools that have a coin creator
+    pub fn fees_for(&self, pool: &Pool) -> PumpSwapFees {
+        PumpSwapFees {
+            lp_fee_basis_points: self.lp_fee_basis_points,
+            protocol_fee_basis_points: self.protocol_fee_basis_points,
+            coin_creator_fee_basis_points: if pool.coin_creator == Pubkey::default() {
+                0
+            } else {
+                self.coin_creator_fee_basis_points
+            },
+        }
+    }
+
+    pub fn pick_protocol_fee_recipient(&self) -> Result<Pubkey> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let recipients: Vec<Pubkey> = self
+            .protocol_fee_recipients
+            .iter()
+            .filter(|recipient| **recipient != Pubkey::default())
+            .copied()
+            .collect();
+        round_robin(&NEXT, &recipients)
+            .ok_or_else(|| anyhow!("pump swap global config has no protocol fee recipients"))
+    }
+
+    pub fn buy_disabled(&self) -> bool {
+        self.disable_flags & (1 << 3) != 0
+    }
+
+    pub fn sell_disabled(&self) -> bool {
+        self.disable_flags & (1 << 4) != 0
+    }
+}
+
+pub fn get_pool_pda(
+    index: u16,
+    creator: &Pubkey,
+    base_mint: &Pubkey,
+    quote_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    let (pool, _bump) = Pubkey::find_program_address(
+        &[
+            b"pool",
+            &index.to_le_bytes(),
+            creator.as_ref(),
+            base_mint.as_ref(),
+            quote_mint.as_ref(),
+        ],
+        program_id,
+    );
+    pool
+}
+
+/// authority the bonding curve migration creates pools with
+pub fn get_pump_pool_authority_pda(mint: &Pubkey) -> Result<Pubkey> {
+    let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+    let (authority, _bump) =
+        Pubkey::find_program_address(&[b"pool-authority", mint.as_ref()], &pump_program);
+    Ok(authority)
+}
+
+/// pool a graduated pump.fun mint was migrated into, paired against wsol
+pub fn get_canonical_pool_pda(base_mint: &Pubkey) -> Result<Pubkey> {
+    let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+    let creator = get_pump_pool_authority_pda(base_mint)?;
+    Ok(get_pool_pda(
+        CANONICAL_POOL_INDEX,
+        &creator,
+        base_mint,
+        &spl_token::native_mint::ID,
+        &program_id,
+    ))
+}
+
+pub fn get_global_config_pda(program_id: &Pubkey) -> Pubkey {
+    let (global_config, _bump) = Pubkey::find_program_address(&[b"global_config"], program_id);
+    global_config
+}
+
+pub fn get_coin_creator_vault_authority_pda(coin_creator: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    let (authority, _bump) =
+        Pubkey::find_program_address(&[b"creator_vault", coin_creator.as_ref()], program_id);
+    authority
+}
+
+pub async fn get_pool(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pool: &Pubkey,
+) -> Result<Pool> {
+    let data = rpc_client.get_account_data(pool).inspect_err(|err| {
+        error!("Failed to get pump swap pool data: {}, err: {}", pool, err);
+    })?;
+    Pool::decode(&data)
+}
+
+pub async fn get_global_config(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+) -> Result<GlobalConfig> {
+    let program_id = Pubkey::from_str(PUMP_SWAP_PROGRAM)?;
+    let global_config = get_global_config_pda(&program_id);
+    let data = rpc_client
+        .get_account_data(&global_config)
+        .inspect_err(|err| {
+            error!(
+                "Failed to get pump swap global config data: {}, err: {}",
+                global_config, err
+            );
+        })?;
+    GlobalConfig::decode(&data)
+}
+
+/// (base, quote) balances held by the pool token accounts
+pub async fn get_pool_reserves(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pool: &Pool,
+) -> Result<(u64, u64)> {
+    let accounts = rpc_client
+        .get_multiple_accounts(&[pool.pool_base_token_account, pool.pool_quote_token_account])?;
+    let mut reserves = [0u64; 2];
+    for (reserve, account) in reserves.iter_mut().zip(accounts) {
+        let account = account.ok_or(anyhow!("NotFoundPool: pool token account not found"))?;
+        *reserve = StateWithExtensions::<Account>::unpack(&account.data)?
+            .base
+            .amount;
+    }
+    info!(
+        "pump swap reserves: base: {}, quote: {}",
+        reserves[0], reserves[1]
+    );
+    Ok((reserves[0], reserves[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{token::tests::mint_with_transfer_fee, tx::SendChannel};
+
+    const BASE_RESERVE: u64 = 1_000_000_000_000;
+    const QUOTE_RESERVE: u64 = 100_000_000_000;
+
+    fn pump_swap() -> PumpSwap {
+        PumpSwap {
+            rpc_nonblocking_client: Arc::new(
+                solana_client::nonblocking::rpc_client::RpcClient::new(
+                    "http://127.0.0.1:8899".to_string(),
+                ),
+            ),
+            keypair: Arc::new(Keypair::new()),
+            rpc_client: None,
+        }
+    }
+
+    fn state(epoch: u64) -> PumpSwapState {
+        let pool = Pool {
+            pool_bump: 255,
+            index: CANONICAL_POOL_INDEX,
+            creator: Pubkey::new_unique(),
+            base_mint: Pubkey::new_unique(),
+            quote_mint: spl_token::native_mint::ID,
+            lp_mint: Pubkey::new_unique(),
+            pool_base_token_account: Pubkey::new_unique(),
+            pool_quote_token_account: Pubkey::new_unique(),
+            lp_supply: 0,
+            coin_creator: Pubkey::new_unique(),
+        };
+        PumpSwapState {
+            pool_id: Pubkey::new_unique(),
+            pool,
+            global_config: GlobalConfig {
+                admin: Pubkey::new_unique(),
+                lp_fee_basis_points: 20,
+                protocol_fee_basis_points: 5,
+                disable_flags: 0,
+                protocol_fee_recipients: [Pubkey::new_unique(); 8],
+                coin_creator_fee_basis_points: 5,
+            },
+            base_reserve: BASE_RESERVE,
+            quote_reserve: QUOTE_RESERVE,
+            base_mint: mint_with_transfer_fee(),
+            base_token_program: spl_token_2022::ID,
+            quote_token_program: spl_token::ID,
+            epoch,
+        }
+    }
+
+    fn swap_config(swap_direction: SwapDirection, in_type: SwapInType) -> SwapConfig {
+        SwapConfig {
+            slippage: 1,
+            swap_direction,
+            in_type,
+            amount: 1.0,
+            channel: SendChannel::Rpc,
+            close_token_account: false,
+        }
+    }
+
+    #[test]
+    fn sell_quotes_what_is_left_after_the_transfer_fee() {
+        let state = state(5);
+        let fees = state.global_config.fees_for(&state.pool);
+        let quote = pump_swap()
+            .quote(
+                &state,
+                &swap_config(SwapDirection::Coin2PC, SwapInType::BaseIn),
+            )
+            .unwrap();
+        // 1% of 1_000_000 is capped at 5_000
+        assert_eq!(quote.transfer_fee, 5_000);
+        assert_eq!(quote.amount_in, 1_000_000);
+        let expected =
+            quote::quote_sell_exact_base_in(BASE_RESERVE, QUOTE_RESERVE, fees, 995_000).unwrap();
+        assert_eq!(quote.amount_out, expected.amount_out);
+        assert_eq!(
+            quote.other_amount_threshold,
+            min_amount_with_slippage(expected.amount_out, 100).unwrap()
+        );
+    }
+
+    #[test]
+    fn sell_uses_the_newer_fee_from_its_epoch() {
+        let state = state(10);
+        let fees = state.global_config.fees_for(&state.pool);
+        let quote = pump_swap()
+            .quote(
+                &state,
+                &swap_config(SwapDirection::Coin2PC, SwapInType::BaseIn),
+            )
+            .unwrap();
+        assert_eq!(quote.transfer_fee, 20_000);
+        let expected =
+            quote::quote_sell_exact_base_in(BASE_RESERVE, QUOTE_RESERVE, fees, 980_000).unwrap();
+        assert_eq!(quote.amount_out, expected.amount_out);
+    }
+
+    #[test]
+    fn exact_out_buy_grosses_up_for_the_transfer_fee() {
+        let state = state(5);
+        let fees = state.global_config.fees_for(&state.pool);
+        let quote = pump_swap()
+            .quote(
+                &state,
+                &swap_config(SwapDirection::PC2Coin, SwapInType::BaseOut),
+            )
+            .unwrap();
+        // exactly the wanted amount arrives, the pool sends the capped fee on top
+        assert_eq!(quote.amount_out, 1_000_000);
+        assert_eq!(quote.transfer_fee, 5_000);
+        let expected =
+            quote::quote_buy_exact_base_out(BASE_RESERVE, QUOTE_RESERVE, fees, 1_005_000).unwrap();
+        assert_eq!(quote.amount_in, expected.amount_in);
+    }
+
+    #[test]
+    fn exact_in_buy_reports_the_fee_withheld() {
+        let state = state(5);
+        let quote = pump_swap()
+            .quote(
+                &state,
+                &swap_config(SwapDirection::PC2Coin, SwapInType::BaseIn),
+            )
+            .unwrap();
+        assert!(quote.amount_in <= 1_000_000_000);
+        assert_eq!(quote.transfer_fee, 5_000);
+        assert_eq!(
+            quote.other_amount_threshold,
+            max_amount_with_slippage(quote.amount_in, 100).unwrap()
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::{Result, anyhow};
+
+use crate::dex::{
+    math::{ceil_div_bps, price_impact},
+    pump_fun::TEN_THOUSAND,
+};
+
+/// Fee settings taken from the PumpSwap GlobalConfig account
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PumpSwapFees {
+    pub lp_fee_basis_points: u64,
+    pub protocol_fee_basis_points: u64,
+    pub coin_creator_fee_basis_points: u64,
+}
+
+impl PumpSwapFees {
+    pub fn total_basis_points(&self) -> u64 {
+        self.lp_fee_basis_points
+            + self.protocol_fee_basis_points
+            + self.coin_creator_fee_basis_points
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PumpSwapQuote {
+    /// quote paid including fees (buy), or base sold (sell)
+    pub amount_in: u64,
+    /// base received (buy), or quote received after fees (sell)
+    pub amount_out: u64,
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+    pub coin_creator_fee: u64,
+    pub price_impact: f64,
+    pub base_reserve_after: u64,
+    pub quote_reserve_after: u64,
+}
+
+/// Base received for spending at most `quote_in`, fees included.
+/// `amount_in` is what the program will actually charge for `amount_out`.
+pub fn quote_buy_exact_quote_in(
+    base_reserve: u64,
+    quote_reserve: u64,
+    fees: PumpSwapFees,
+    quote_in: u64,
+) -> Result<PumpSwapQuote> {
+    check_reserves(base_reserve, quote_reserve)?;
+    // strip the fees from the budget before running it through the pool
+    let effective_quote = quote_in as u128 * TEN_THOUSAND as u128
+        / (TEN_THOUSAND + fees.total_basis_points()) as u128;
+    let base_out =
+        base_reserve as u128 * effective_quote / (quote_reserve as u128 + effective_quote);
+    if base_out == 0 {
+        return Err(anyhow!(
+            "QuoteError: quote amount {} buys zero base",
+            quote_in
+        ));
+    }
+
+    // each fee rounds up on the exact-out path, which can cost a few units
+    // more than the budget, so search for the largest base amount that fits
+    let (mut low, mut high) = (1, base_out as u64);
+    let mut best = None;
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let quote = quote_buy_exact_base_out(base_reserve, quote_reserve, fees, mid)?;
+        if quote.amount_in <= quote_in {
+            best = Some(quote);
+            low = mid + 1;
+        } else {
+            high = mid - 1;
+        }
+    }
+    best.ok_or(anyhow!(
+        "QuoteError: quote amount {} buys zero base",
+        quote_in
+    ))
+}
+
+/// Quote needed to buy exactly `base_out`, fees included.
+/// This is the value the program checks against `max_quote_amount_in`.
+pub fn quote_buy_exact_base_out(
+    base_reserve: u64,
+    quote_reserve: u64,
+    fees: PumpSwapFees,
+    base_out: u64,
+) -> Result<PumpSwapQuote> {
+    check_reserves(base_reserve, quote_reserve)?;
+    if base_out == 0 {
+        return Err(anyhow!("QuoteError: base amount is zero"));
+    }
+    if base_out >= base_reserve {
+        return Err(anyhow!(
+            "QuoteError: base amount {} exceeds pool reserves {}",
+            base_out,
+            base_reserve
+        ));
+    }
+
+    let quote_amount =
+        (quote_reserve as u128 * base_out as u128).div_ceil((base_reserve - base_out) as u128);
+    let quote_amount = u64::try_from(quote_amount)?;
+    let lp_fee = ceil_div_bps(quote_amount, fees.lp_fee_basis_points);
+    let protocol_fee = ceil_div_bps(quote_amount, fees.protocol_fee_basis_points);
+    let coin_creator_fee = ceil_div_bps(quote_amount, fees.coin_creator_fee_basis_points);
+
+    Ok(PumpSwapQuote {
+        amount_in: quote_amount + lp_fee + protocol_fee + coin_creator_fee,
+        amount_out: base_out,
+        lp_fee,
+        protocol_fee,
+        coin_creator_fee,
+        price_impact: price_impact(
+            base_reserve as u128,
+            quote_reserve as u128,
+            base_out as u128,
+            quote_amount as u128,
+        ),
+        base_reserve_after: base_reserve - base_out,
+        // protocol and creator fees are moved out of the pool, the lp fee stays
+        quote_reserve_after: quote_reserve + quote_amount + lp_fee,
+    })
+}
+
+/// Quote received for selling exactly `base_in`, fees deducted.
+/// This is the value the program checks against `min_quote_amount_out`.
+pub fn quote_sell_exact_base_in(
+    base_reserve: u64,
+    quote_reserve: u64,
+    fees: PumpSwapFees,
+    base_in: u64,
+) -> Result<PumpSwapQuote> {
+    check_reserves(base_reserve, quote_reserve)?;
+    if base_in == 0 {
+        return Err(anyhow!("QuoteError: base amount is zero"));
+    }
+
+    let quote_amount =
+        quote_reserve as u128 * base_in as u128 / (base_reserve as u128 + base_in as u128);
+    let quote_amount = u64::try_from(quote_amount)?;
+    let lp_fee = ceil_div_bps(quote_amount, fees.lp_fee_basis_points);
+    let protocol_fee = ceil_div_bps(quote_amount, fees.protocol_fee_basis_points);
+    let coin_creator_fee = ceil_div_bps(quote_amount, fees.coin_creator_fee_basis_points);
+    let amount_out = quote_amount
+        .checked_sub(lp_fee + protocol_fee + coin_creator_fee)
+        .ok_or(anyhow!(
+            "QuoteError: fees exceed quote output {}",
+            quote_amount
+        ))?;
+
+    Ok(PumpSwapQuote {
+        amount_in: base_in,
+        amount_out,
+        lp_fee,
+        protocol_fee,
+        coin_creator_fee,
+        price_impact: price_impact(
+            base_reserve as u128,
+            quote_reserve as u128,
+            base_in as u128,
+            quote_amount as u128,
+        ),
+        base_reserve_after: base_reserve + base_in,
+        quote_reserve_after: quote_reserve - quote_amount + lp_fee,
+    })
+}
+
+fn check_reserves(base_reserve: u64, quote_reserve: u64) -> Result<()> {
+    if base_reserve == 0 || quote_reserve == 0 {
+        return Err(anyhow!("QuoteError: pool has empty reserves"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE_RESERVE: u64 = 1_000_000_000;
+    const QUOTE_RESERVE: u64 = 100_000_000;
+
+    fn fees() -> PumpSwapFees {
+        PumpSwapFees {
+            lp_fee_basis_points: 20,
+            protocol_fee_basis_points: 5,
+            coin_creator_fee_basis_points: 5,
+        }
+    }
+
+    #[test]
+    fn fees_round_up() {
+        assert_eq!(ceil_div_bps(10_000, 20), 20);
+        assert_eq!(ceil_div_bps(10_001, 20), 21);
+        assert_eq!(ceil_div_bps(1, 5), 1);
+        assert_eq!(ceil_div_bps(0, 20), 0);
+        assert_eq!(ceil_div_bps(1_000, 0), 0);
+    }
+
+    #[test]
+    fn buy_exact_base_out() {
+        let quote =
+            quote_buy_exact_base_out(BASE_RESERVE, QUOTE_RESERVE, fees(), 1_000_000).unwrap();
+        // ceil(100_000_000 * 1_000_000 / 999_000_000) = 100_101
+        assert_eq!(quote.lp_fee, 201);
+        assert_eq!(quote.protocol_fee, 51);
+        assert_eq!(quote.coin_creator_fee, 51);
+        assert_eq!(quote.amount_in, 100_101 + 201 + 51 + 51);
+        assert_eq!(quote.amount_out, 1_000_000);
+        assert_eq!(quote.base_reserve_after, 999_000_000);
+        // only the lp fee stays in the pool
+        assert_eq!(quote.quote_reserve_after, QUOTE_RESERVE + 100_101 + 201);
+        assert!(quote.price_impact > 0.0 && quote.price_impact < 0.01);
+    }
+
+    #[test]
+    fn buy_exact_base_out_rejects_bad_amounts() {
+        assert!(quote_buy_exact_base_out(BASE_RESERVE, QUOTE_RESERVE, fees(), 0).is_err());
+        assert!(
+            quote_buy_exact_base_out(BASE_RESERVE, QUOTE_RESERVE, fees(), BASE_RESERVE).is_err()
+        );
+        assert!(quote_buy_exact_base_out(0, QUOTE_RESERVE, fees(), 1).is_err());
+    }
+
+    #[test]
+    fn buy_exact_quote_in_stays_within_budget() {
+        // the largest base amount a 100_404 budget covers once every fee rounds up
+        let quote = quote_buy_exact_quote_in(BASE_RESERVE, QUOTE_RESERVE, fees(), 100_404).unwrap();
+        assert_eq!(quote.amount_out, 1_000_008);
+        assert_eq!(quote.amount_in, 100_404);
+        let next =
+            quote_buy_exact_base_out(BASE_RESERVE, QUOTE_RESERVE, fees(), 1_000_009).unwrap();
+        assert!(next.amount_in > 100_404);
+
+        let quote = quote_buy_exact_quote_in(BASE_RESERVE, QUOTE_RESERVE, fees(), 100_000).unwrap();
+        assert_eq!(quote.amount_out, 996_006);
+        assert_eq!(quote.amount_in, 100_000);
+    }
+
+    #[test]
+    fn buy_dust_buys_nothing() {
+        assert!(quote_buy_exact_quote_in(100, 100, fees(), 1).is_err());
+        assert!(quote_buy_exact_quote_in(BASE_RESERVE, QUOTE_RESERVE, fees(), 0).is_err());
+    }
+
+    #[test]
+    fn sell_exact_base_in() {
+        let quote =
+            quote_sell_exact_base_in(BASE_RESERVE, QUOTE_RESERVE, fees(), 1_000_000).unwrap();
+        // floor(100_000_000 * 1_000_000 / 1_001_000_000) = 99_900
+        assert_eq!(quote.lp_fee, 200);
+        assert_eq!(quote.protocol_fee, 50);
+        assert_eq!(quote.coin_creator_fee, 50);
+        assert_eq!(quote.amount_out, 99_900 - 300);
+        assert_eq!(quote.amount_in, 1_000_000);
+        assert_eq!(quote.base_reserve_after, 1_001_000_000);
+        assert_eq!(quote.quote_reserve_after, QUOTE_RESERVE - 99_900 + 200);
+    }
+
+    #[test]
+    fn sell_dust_is_eaten_by_fees() {
+        // 2 base buys 1 quote, and each of the three fees rounds up to 1
+        assert!(quote_sell_exact_base_in(100, 100, fees(), 2).is_err());
+        assert!(quote_sell_exact_base_in(BASE_RESERVE, QUOTE_RESERVE, fees(), 0).is_err());
+        let quote = quote_sell_exact_base_in(100, 100, PumpSwapFees::default(), 2).unwrap();
+        assert_eq!(quote.amount_out, 1);
+    }
+
+    #[test]
+    fn no_creator_fee_without_creator() {
+        let fees = PumpSwapFees {
+            coin_creator_fee_basis_points: 0,
+            ..fees()
+        };
+        assert_eq!(fees.total_basis_points(), 25);
+        let quote = quote_sell_exact_base_in(BASE_RESERVE, QUOTE_RESERVE, fees, 1_000_000).unwrap();
+        assert_eq!(quote.coin_creator_fee, 0);
+        assert_eq!(quote.amount_out, 99_900 - 250);
+    }
+}
@@ -1,9 +1,14 @@
 use crate::{
     core::{
+        ata::{self, SwapAccounts},
         token::{get_account_info, get_associated_token_address, get_mint_info},
-        tx,
     },
-    engine::swap::{SwapDirection, SwapConfig},
+    dex::{
+        math::price_impact,
+        pump_fun::{max_amount_with_slippage, min_amount_with_slippage},
+        traits::Dex,
+    },
+    engine::swap::{self, SwapConfig, SwapDirection, SwapInType, SwapQuote},
 };
 
 use anyhow::{Context, Result, anyhow};
@@ -11,19 +16,26 @@ use bytemuck;
 use raydium_amm::accounts::AmmInfo;
 use reqwest::Proxy;
 use serde::Deserialize;
-use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
 use solana_sdk::{
     instruction::Instruction, program_pack::Pack, pubkey::Pubkey, signature::Keypair,
     signer::Signer,
 };
 
-use raydium_amm::instructions::{SwapBaseIn, swap_base_in, swap_base_out, SwapBaseInInstructionArgs, SwapBaseOutInstructionArgs, SwapBaseOut};
+use log::info;
+use raydium_amm::instructions::{
+    SwapBaseIn, SwapBaseInInstructionArgs, SwapBaseOut, SwapBaseOutInstructionArgs, swap_base_in,
+    swap_base_out,
+};
 use solana_program::system_instruction;
 use spl_associated_token_account::instruction::create_associated_token_account;
 use spl_token::{amount_to_ui_amount, state::Account, ui_amount_to_amount};
 use spl_token_client::token::TokenError;
 use std::{str::FromStr, sync::Arc};
-use log::info;
 
 pub const AMM_PROGRAM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
 
@@ -107,59 +119,449 @@ impl Raydium {
         }
     }
 
-    pub async fn swap(
-        &self,
-        swap_config: SwapConfig,
-        amm_pool_id: Pubkey,
-        pool_state: AmmInfo,
-    ) -> Result<Vec<String>> {
-        info!("[SWAP IN RAYDIUM]({}) => ");
+    /// swaps in `pool_id` when set, otherwise in the wsol pool found for `mint`
+    pub async fn swap(&self, mint: &str, swap_config: SwapConfig) -> Result<Vec<String>> {
+        info!("[SWAP IN RAYDIUM]({:?}) => ", self.pool_id);
+        let mint =
+            Pubkey::from_str(mint).map_err(|e| anyhow!("failed to parse mint pubkey: {}", e))?;
+        let client = self.rpc_client.clone().unwrap();
+        swap::execute_swap(self, &client, &self.keypair, &mint, &swap_config).await
+    }
+}
+
+pub struct RaydiumState {
+    pub pool_id: Pubkey,
+    pub pool_state: AmmInfo,
+    pub market_keys: MarketKeys,
+    /// live vault balances, pnl not yet taken is still included
+    pub coin_vault_amount: u64,
+    pub pc_vault_amount: u64,
+}
+
+impl RaydiumState {
+    /// the non-wsol side of the pool
+    pub fn mint(&self) -> Pubkey {
+        if self.pool_state.coin_vault_mint == spl_token::native_mint::ID {
+            self.pool_state.pc_vault_mint
+        } else {
+            self.pool_state.coin_vault_mint
+        }
+    }
+
+    /// maps a buy (pc2coin, sol in) or sell of `mint` onto the pool's coin/pc sides
+    pub fn pool_direction(&self, swap_direction: SwapDirection) -> SwapDirection {
+        let sol_is_coin = self.pool_state.coin_vault_mint == spl_token::native_mint::ID;
+        match (swap_direction, sol_is_coin) {
+            (SwapDirection::PC2Coin, true) | (SwapDirection::Coin2PC, false) => {
+                SwapDirection::Coin2PC
+            }
+            (SwapDirection::PC2Coin, false) | (SwapDirection::Coin2PC, true) => {
+                SwapDirection::PC2Coin
+            }
+        }
+    }
+}
+
+pub async fn load_pool(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pool_id: Pubkey,
+    pool_state: AmmInfo,
+) -> Result<RaydiumState> {
+    let market_keys = get_market_keys(
+        rpc_client.clone(),
+        &pool_state.market_program,
+        &pool_state.market,
+    )
+    .await?;
+    let (coin_vault_amount, pc_vault_amount) = get_vault_amounts(rpc_client, &pool_state).await?;
+    Ok(RaydiumState {
+        pool_id,
+        pool_state,
+        market_keys,
+        coin_vault_amount,
+        pc_vault_amount,
+    })
+}
+
+pub async fn get_vault_amounts(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    pool_state: &AmmInfo,
+) -> Result<(u64, u64)> {
+    let accounts =
+        rpc_client.get_multiple_accounts(&[pool_state.coin_vault, pool_state.pc_vault])?;
+    let mut amounts = [0u64; 2];
+    for (amount, account) in amounts.iter_mut().zip(accounts) {
+        let account = account.ok_or(anyhow!("NotFoundPool: pool vault not found"))?;
+        // vaults are spl token accounts, anything shorter isn't one
+        let data = account
+            .data
+            .get(..Account::LEN)
+            .ok_or_else(|| anyhow!("InvalidPool: vault data is {} bytes", account.data.len()))?;
+        *amount = Account::unpack(data)?.amount;
+    }
+    info!("raydium vaults: coin: {}, pc: {}", amounts[0], amounts[1]);
+    Ok((amounts[0], amounts[1]))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaydiumQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub swap_fee: u64,
+    pub price_impact: f64,
+}
+
+/// Vault balances minus the pnl the pool still owes, as the program prices swaps
+pub fn total_without_take_pnl(
+    pool_state: &AmmInfo,
+    coin_vault_amount: u64,
+    pc_vault_amount: u64,
+) -> Result<(u64, u64)> {
+    let total_coin = coin_vault_amount
+        .checked_sub(pool_state.state_data.need_take_pnl_coin)
+        .ok_or(anyhow!("QuoteError: coin vault below need_take_pnl_coin"))?;
+    let total_pc = pc_vault_amount
+        .checked_sub(pool_state.state_data.need_take_pnl_pc)
+        .ok_or(anyhow!("QuoteError: pc vault below need_take_pnl_pc"))?;
+    if total_coin == 0 || total_pc == 0 {
+        return Err(anyhow!("QuoteError: pool has empty reserves"));
+    }
+    Ok((total_coin, total_pc))
+}
+
+/// Amount out for exactly `amount_in`, the `swap_base_in` path of the program
+pub fn quote_base_in(
+    pool_state: &AmmInfo,
+    coin_vault_amount: u64,
+    pc_vault_amount: u64,
+    pool_direction: SwapDirection,
+    amount_in: u64,
+) -> Result<RaydiumQuote> {
+    let (total_coin, total_pc) =
+        total_without_take_pnl(pool_state, coin_vault_amount, pc_vault_amount)?;
+    let (reserve_in, reserve_out) = match pool_direction {
+        SwapDirection::Coin2PC => (total_coin as u128, total_pc as u128),
+        SwapDirection::PC2Coin => (total_pc as u128, total_coin as u128),
+    };
+    let fees = &pool_state.fees;
+    let (swap_fee, _) = checked_ceil_div(
+        amount_in as u128 * fees.swap_fee_numerator as u128,
+        fees.swap_fee_denominator as u128,
+    )?;
+    let amount_in_after_fee = amount_in as u128 - swap_fee;
+    let amount_out = reserve_out * amount_in_after_fee / (reserve_in + amount_in_after_fee);
+
+    Ok(RaydiumQuote {
+        amount_in,
+        amount_out: u64::try_from(amount_out)?,
+        swap_fee: u64::try_from(swap_fee)?,
+        price_impact: price_impact(reserve_in, reserve_out, amount_in_after_fee, amount_out),
+    })
+}
+
+/// Amount in, fee included, for exactly `amount_out`, the `swap_base_out` path of the program
+pub fn quote_base_out(
+    pool_state: &AmmInfo,
+    coin_vault_amount: u64,
+    pc_vault_amount: u64,
+    pool_direction: SwapDirection,
+    amount_out: u64,
+) -> Result<RaydiumQuote> {
+    let (total_coin, total_pc) =
+        total_without_take_pnl(pool_state, coin_vault_amount, pc_vault_amount)?;
+    let (reserve_in, reserve_out) = match pool_direction {
+        SwapDirection::Coin2PC => (total_coin as u128, total_pc as u128),
+        SwapDirection::PC2Coin => (total_pc as u128, total_coin as u128),
+    };
+    if amount_out as u128 >= reserve_out {
+        return Err(anyhow!(
+            "QuoteError: amount out {} exceeds pool reserves {}",
+            amount_out,
+            reserve_out
+        ));
+    }
+    let (amount_in_before_fee, _) = checked_ceil_div(
+        reserve_in * amount_out as u128,
+        reserve_out - amount_out as u128,
+    )?;
+    let fees = &pool_state.fees;
+    let (amount_in, _) = checked_ceil_div(
+        amount_in_before_fee * fees.swap_fee_denominator as u128,
+        (fees.swap_fee_denominator - fees.swap_fee_numerator) as u128,
+    )?;
+
+    Ok(RaydiumQuote {
+        amount_in: u64::try_from(amount_in)?,
+        amount_out,
+        swap_fee: u64::try_from(amount_in - amount_in_before_fee)?,
+        price_impact: price_impact(
+            reserve_in,
+            reserve_out,
+            amount_in_before_fee,
+            amount_out as u128,
+        ),
+    })
+}
+
+// the program's CheckedCeilDiv: rounds up, except that a quotient below 1
+// rounds to nearest instead of always becoming 1
+fn checked_ceil_div(numerator: u128, denominator: u128) -> Result<(u128, u128)> {
+    if denominator == 0 {
+        return Err(anyhow!("QuoteError: division by zero"));
+    }
+    let mut quotient = numerator / denominator;
+    if quotient == 0 {
+        return if numerator * 2 >= denominator {
+            Ok((1, 0))
+        } else {
+            Ok((0, 0))
+        };
+    }
+    let mut denominator = denominator;
+    if numerator % denominator > 0 {
+        quotient += 1;
+        denominator = numerator / quotient;
+        if numerator % quotient > 0 {
+            denominator += 1;
+        }
+    }
+    Ok((quotient, denominator))
+}
+
+impl Dex for Raydium {
+    type State = RaydiumState;
+
+    async fn load_state(&self, mint: &Pubkey) -> Result<RaydiumState> {
+        let client = self.rpc_client.clone().unwrap();
+        let (pool_id, pool_state) = match &self.pool_id {
+            Some(pool_id) => get_pool_state(client.clone(), Some(pool_id.as_str()), None).await?,
+            None => get_pool_state_by_mint(client.clone(), &mint.to_string()).await?,
+        };
+        load_pool(client, pool_id, pool_state).await
+    }
+
+    fn quote(&self, state: &RaydiumState, swap_config: &SwapConfig) -> Result<SwapQuote> {
         let slippage_bps = swap_config.slippage * 100;
+        let pool_direction = state.pool_direction(swap_config.swap_direction);
+        let (input_decimals, output_decimals) = match pool_direction {
+            SwapDirection::Coin2PC => {
+                (state.pool_state.coin_decimals, state.pool_state.pc_decimals)
+            }
+            SwapDirection::PC2Coin => {
+                (state.pool_state.pc_decimals, state.pool_state.coin_decimals)
+            }
+        };
+        match swap_config.in_type {
+            SwapInType::BaseIn => {
+                let amount_in = ui_amount_to_amount(swap_config.amount, input_decimals as u8);
+                let quote = quote_base_in(
+                    &state.pool_state,
+                    state.coin_vault_amount,
+                    state.pc_vault_amount,
+                    pool_direction,
+                    amount_in,
+                )?;
+                Ok(SwapQuote {
+                    amount_in: quote.amount_in,
+                    amount_out: quote.amount_out,
+                    transfer_fee: 0,
+                    other_amount_threshold: min_amount_with_slippage(
+                        quote.amount_out,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+            SwapInType::BaseOut => {
+                let amount_out = ui_amount_to_amount(swap_config.amount, output_decimals as u8);
+                let quote = quote_base_out(
+                    &state.pool_state,
+                    state.coin_vault_amount,
+                    state.pc_vault_amount,
+                    pool_direction,
+                    amount_out,
+                )?;
+                Ok(SwapQuote {
+                    amount_in: quote.amount_in,
+                    amount_out: quote.amount_out,
+                    transfer_fee: 0,
+                    other_amount_threshold: max_amount_with_slippage(
+                        quote.amount_in,
+                        slippage_bps,
+                    )?,
+                    price_impact: quote.price_impact,
+                })
+            }
+        }
+    }
+
+    async fn build_swap_instructions(
+        &self,
+        state: &RaydiumState,
+        swap_config: &SwapConfig,
+    ) -> Result<Vec<Instruction>> {
         let owner = self.keypair.pubkey();
-        let program_id = spl_token::ID;
         let native_mint = spl_token::native_mint::ID;
-        let mint = pool_state.coin_vault_mint;
-
-        let (token_in, token_out, user_input_token, swap_base_in) = match (
-            swap_config.swap_direction.clone(),
-            pool_state.coin_vault_mint == native_mint,
-        ) {
-            (SwapDirection::Buy, true) => (native_mint, mint, pool_state.coin_vault, true),
-            (SwapDirection::Buy, false) => (native_mint, mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, true) => (mint, native_mint, pool_state.pc_vault, true),
-            (SwapDirection::Sell, false) => (mint, native_mint, pool_state.coin_vault, true),
+        let mint = state.mint();
+        let (token_in, token_out) = match swap_config.swap_direction {
+            SwapDirection::PC2Coin => (native_mint, mint),
+            SwapDirection::Coin2PC => (mint, native_mint),
         };
-
+        let quote = self.quote(state, swap_config)?;
         info!(
-            "token_in:{}, token_out:{}, user_input_token:{}, swap_base_in:{}",
-            token_in, token_out, user_input_token, swap_base_in
+            "token_in:{}, token_out:{}, amount_in:{}, threshold:{}, price_impact:{:.4}",
+            token_in, token_out, quote.amount_in, quote.other_amount_threshold, quote.price_impact
         );
 
-        let in_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_in,
-            &owner,
-        );
-        let out_ata = get_associated_token_address(
-            self.rpc_nonblocking_client.clone(),
-            self.keypair.clone(),
-            &token_out,
-            &owner,
+        let amm_program = Pubkey::from_str(AMM_PROGRAM)?;
+        let (amm_authority, _bump) =
+            Pubkey::find_program_address(&[b"amm authority"], &amm_program);
+        // base in: exact amount in, minimum out; base out: maximum in, exact amount out
+        let (amount_in, amount_out) = match swap_config.in_type {
+            SwapInType::BaseIn => (quote.amount_in, quote.other_amount_threshold),
+            SwapInType::BaseOut => (quote.other_amount_threshold, quote.amount_out),
+        };
+
+        // amm v4 only handles classic spl-token mints
+        let mut swap_accounts = SwapAccounts::new();
+        let (in_ata, out_ata) = match swap_config.swap_direction {
+            SwapDirection::PC2Coin => (
+                swap_accounts.wrap_sol(&owner, amount_in),
+                swap_accounts.create_ata(&owner, &mint, &spl_token::ID),
+            ),
+            SwapDirection::Coin2PC => {
+                // an exact-out sell may leave tokens behind, only close on exact-in
+                if swap_config.close_token_account
+                    && swap_config.in_type == SwapInType::BaseIn
+                    && ata::is_full_sell(
+                        self.rpc_nonblocking_client.clone(),
+                        self.keypair.clone(),
+                        &mint,
+                        &spl_token::ID,
+                        amount_in,
+                    )
+                    .await?
+                {
+                    swap_accounts.close_ata(&owner, &mint, &spl_token::ID);
+                }
+                (
+                    get_associated_token_address(
+                        self.rpc_nonblocking_client.clone(),
+                        self.keypair.clone(),
+                        &mint,
+                        &owner,
+                        &spl_token::ID,
+                    ),
+                    swap_accounts.receive_sol(&owner),
+                )
+            }
+        };
+        let pool_state = &state.pool_state;
+        let market_keys = &state.market_keys;
+        let swap_instruction = amm_swap(
+            spl_token::ID,
+            state.pool_id,
+            amm_authority,
+            pool_state.open_orders,
+            pool_state.target_orders,
+            pool_state.coin_vault,
+            pool_state.pc_vault,
+            pool_state.market_program,
+            pool_state.market,
+            market_keys.bids,
+            market_keys.asks,
+            market_keys.event_queue,
+            market_keys.coin_vault,
+            market_keys.pc_vault,
+            market_keys.vault_signer,
+            in_ata,
+            out_ata,
+            owner,
+            amount_in,
+            amount_out,
+            swap_config.in_type == SwapInType::BaseIn,
         );
+        Ok(swap_accounts.wrap(vec![swap_instruction]))
+    }
+
+    fn lookup_table_addresses(&self, state: &RaydiumState) -> Vec<Pubkey> {
+        let amm_program = Pubkey::from_str(AMM_PROGRAM).unwrap();
+        let (amm_authority, _bump) =
+            Pubkey::find_program_address(&[b"amm authority"], &amm_program);
+        let pool_state = &state.pool_state;
+        let market_keys = &state.market_keys;
+        vec![
+            amm_program,
+            amm_authority,
+            spl_token::ID,
+            state.pool_id,
+            pool_state.open_orders,
+            pool_state.target_orders,
+            pool_state.coin_vault,
+            pool_state.pc_vault,
+            pool_state.market_program,
+            pool_state.market,
+            market_keys.bids,
+            market_keys.asks,
+            market_keys.event_queue,
+            market_keys.coin_vault,
+            market_keys.pc_vault,
+            market_keys.vault_signer,
+        ]
+    }
+}
+
+/// OpenBook market accounts a v4 swap still has to pass along
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarketKeys {
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub event_queue: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+}
 
-        let mut create_instruction = None;
-        let mut close_instruction = None;
+// offsets into the serum/openbook MarketState, after the 5 byte "serum" head padding
+const MARKET_VAULT_SIGNER_NONCE_OFFSET: usize = 45;
+const MARKET_COIN_VAULT_OFFSET: usize = 117;
+const MARKET_PC_VAULT_OFFSET: usize = 165;
+const MARKET_EVENT_QUEUE_OFFSET: usize = 253;
+const MARKET_BIDS_OFFSET: usize = 285;
+const MARKET_ASKS_OFFSET: usize = 317;
 
-        tx::new_signed_and_send(&client, &self.keypair, instructions, swap_config.use_jito).await
+pub async fn get_market_keys(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    market_program: &Pubkey,
+    market: &Pubkey,
+) -> Result<MarketKeys> {
+    let data = rpc_client.get_account_data(market)?;
+    if data.len() < MARKET_ASKS_OFFSET + 32 {
+        return Err(anyhow!("NotFoundMarket: invalid market account {}", market));
     }
+    let read_pubkey = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]);
+    let nonce = u64::from_le_bytes(
+        data[MARKET_VAULT_SIGNER_NONCE_OFFSET..MARKET_VAULT_SIGNER_NONCE_OFFSET + 8].try_into()?,
+    );
+    let vault_signer =
+        Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], market_program)?;
+
+    Ok(MarketKeys {
+        bids: read_pubkey(MARKET_BIDS_OFFSET)?,
+        asks: read_pubkey(MARKET_ASKS_OFFSET)?,
+        event_queue: read_pubkey(MARKET_EVENT_QUEUE_OFFSET)?,
+        coin_vault: read_pubkey(MARKET_COIN_VAULT_OFFSET)?,
+        pc_vault: read_pubkey(MARKET_PC_VAULT_OFFSET)?,
+        vault_signer,
+    })
 }
 
 pub fn amm_swap(
-    amm_program: Pubkey,
+    token_program: Pubkey,
     amm_pool: Pubkey,
     amm_authority: Pubkey,
     amm_open_orders: Pubkey,
+    amm_target_orders: Pubkey,
     amm_coin_vault: Pubkey,
     amm_pc_vault: Pubkey,
     market_program: Pubkey,
@@ -179,16 +581,16 @@ pub fn amm_swap(
 ) -> Instruction {
     match is_swap_base_in {
         true => {
-            let args = SwapBaseInInstructionArgs{
+            let args = SwapBaseInInstructionArgs {
                 amount_in,
-                minimum_amount_out:amount_out,
+                minimum_amount_out: amount_out,
             };
             let sbi = SwapBaseIn {
-                token_program: amm_program,
+                token_program,
                 amm: amm_pool,
                 amm_authority,
                 amm_open_orders,
-                amm_target_orders: Default::default(),
+                amm_target_orders,
                 pool_coin_token_account: amm_coin_vault,
                 pool_pc_token_account: amm_pc_vault,
                 serum_program: market_program,
@@ -203,19 +605,19 @@ pub fn amm_swap(
                 uer_destination_token_account: user_token_destination,
                 user_source_owner: user_source_owner,
             };
-            sbi.instruction_with_remaining_accounts(args,&[])
+            sbi.instruction_with_remaining_accounts(args, &[])
         }
         false => {
-            let args = SwapBaseOutInstructionArgs{
+            let args = SwapBaseOutInstructionArgs {
                 max_amount_in: amount_in,
                 amount_out: amount_out,
             };
             let sbi = SwapBaseOut {
-                token_program: amm_program,
+                token_program,
                 amm: amm_pool,
                 amm_authority,
                 amm_open_orders,
-                amm_target_orders: Default::default(),
+                amm_target_orders,
                 pool_coin_token_account: amm_coin_vault,
                 pool_pc_token_account: amm_pc_vault,
                 serum_program: market_program,
@@ -230,7 +632,7 @@ pub fn amm_swap(
                 uer_destination_token_account: user_token_destination,
                 user_source_owner: user_source_owner,
             };
-            sbi.instruction_with_remaining_accounts(args,&[])
+            sbi.instruction_with_remaining_accounts(args, &[])
         }
     }
 }
@@ -243,14 +645,15 @@ pub async fn get_pool_state(
     if let Some(pool_id) = pool_id {
         info!("[FIND POOL STATE BY pool_id]: {}", pool_id);
         let amm_pool_id = Pubkey::from_str(pool_id)?;
-        let pool_data = common::rpc::get_account(&rpc_client, &amm_pool_id)?
-            .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+        let pool_data = rpc_client
+            .get_account_data(&amm_pool_id)
+            .map_err(|e| anyhow!("NotFoundPool: pool state not found: {}", e))?;
         let pool_state: &AmmInfo =
             bytemuck::from_bytes(&pool_data[0..core::mem::size_of::<AmmInfo>()]);
         Ok((amm_pool_id, *pool_state))
     } else if let Some(mint) = mint {
         // find pool by mint via rpc
-        if let Ok(pool_state) = get_pool_state_by_mint(rpc_client.clone(), mint, logger).await {
+        if let Ok(pool_state) = get_pool_state_by_mint(rpc_client.clone(), mint).await {
             return Ok(pool_state);
         }
         // find pool by mint via raydium api
@@ -261,8 +664,9 @@ pub async fn get_pool_state(
                 .ok_or(anyhow!("NotFoundPool: pool not found in raydium api"))?;
             let amm_pool_id = Pubkey::from_str(&pool.id)?;
             info!("[FIND POOL STATE BY raydium api]: {}", amm_pool_id);
-            let pool_data = common::rpc::get_account(&rpc_client, &amm_pool_id)?
-                .ok_or(anyhow!("NotFoundPool: pool state not found"))?;
+            let pool_data = rpc_client
+                .get_account_data(&amm_pool_id)
+                .map_err(|e| anyhow!("NotFoundPool: pool state not found: {}", e))?;
             let pool_state: &AmmInfo =
                 bytemuck::from_bytes(&pool_data[0..core::mem::size_of::<AmmInfo>()]);
 
@@ -317,9 +721,17 @@ pub async fn get_pool_state_by_mint(
                 RpcFilterType::DataSize(pool_len),
             ]),
         };
-        let pools =
-            common::rpc::get_program_accounts_with_filters(&rpc_client, amm_program, filters)
-                .unwrap();
+        let pools = rpc_client.get_program_accounts_with_config(
+            &amm_program,
+            RpcProgramAccountsConfig {
+                filters,
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?;
         if !pools.is_empty() {
             found_pools = Some(pools);
             break;
@@ -329,7 +741,8 @@ pub async fn get_pool_state_by_mint(
     match found_pools {
         Some(pools) => {
             let pool = &pools[0];
-            let pool_state = AmmInfo::load_from_bytes(&pools[0].1.data)?;
+            let pool_state: &AmmInfo =
+                bytemuck::from_bytes(&pool.1.data[0..core::mem::size_of::<AmmInfo>()]);
             Ok((pool.0, *pool_state))
         }
         None => Err(anyhow!("NotFoundPool: pool state not found")),
@@ -363,3 +776,159 @@ pub async fn get_pool_info(mint1: &str, mint2: &str) -> Result<PoolData> {
         .context("Failed to parse pool info JSON")?;
     Ok(result.data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COIN_VAULT: u64 = 1_000_500_000;
+    const PC_VAULT: u64 = 50_000_000_000;
+
+    // 0.25% swap fee and 500_000 coin of pnl still owed, like a live pool
+    fn pool_state() -> AmmInfo {
+        let mut pool_state = AmmInfo::default();
+        pool_state.fees.swap_fee_numerator = 25;
+        pool_state.fees.swap_fee_denominator = 10_000;
+        pool_state.state_data.need_take_pnl_coin = 500_000;
+        pool_state
+    }
+
+    #[test]
+    fn ceil_div_rounds_up() {
+        assert_eq!(checked_ceil_div(25_000_000, 10_000).unwrap().0, 2_500);
+        assert_eq!(checked_ceil_div(25_000_025, 10_000).unwrap().0, 2_501);
+        assert_eq!(checked_ceil_div(7, 2).unwrap(), (4, 2));
+        // quotients below 1 round to nearest
+        assert_eq!(checked_ceil_div(2, 3).unwrap().0, 1);
+        assert_eq!(checked_ceil_div(1, 3).unwrap().0, 0);
+        assert_eq!(checked_ceil_div(0, 3).unwrap().0, 0);
+        assert!(checked_ceil_div(1, 0).is_err());
+    }
+
+    #[test]
+    fn reserves_exclude_pnl() {
+        let pool_state = pool_state();
+        assert_eq!(
+            total_without_take_pnl(&pool_state, COIN_VAULT, PC_VAULT).unwrap(),
+            (1_000_000_000, PC_VAULT)
+        );
+        assert!(total_without_take_pnl(&pool_state, 499_999, PC_VAULT).is_err());
+        assert!(total_without_take_pnl(&pool_state, 500_000, PC_VAULT).is_err());
+        assert!(total_without_take_pnl(&pool_state, COIN_VAULT, 0).is_err());
+    }
+
+    #[test]
+    fn base_in() {
+        let quote = quote_base_in(
+            &pool_state(),
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::Coin2PC,
+            1_000_000,
+        )
+        .unwrap();
+        assert_eq!(quote.swap_fee, 2_500);
+        // 50_000_000_000 * 997_500 / (1_000_000_000 + 997_500)
+        assert_eq!(quote.amount_out, 49_825_299);
+        assert_eq!(quote.amount_in, 1_000_000);
+        assert!(quote.price_impact > 0.0 && quote.price_impact < 0.01);
+
+        let quote = quote_base_in(
+            &pool_state(),
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::Coin2PC,
+            1_000_001,
+        )
+        .unwrap();
+        assert_eq!(quote.swap_fee, 2_501);
+    }
+
+    #[test]
+    fn base_in_dust() {
+        let pool_state = pool_state();
+        let quote =
+            quote_base_in(&pool_state, COIN_VAULT, PC_VAULT, SwapDirection::Coin2PC, 0).unwrap();
+        assert_eq!((quote.swap_fee, quote.amount_out), (0, 0));
+        // a fee of 0.75 rounds to 1, 0.025 rounds to 0
+        let quote = quote_base_in(
+            &pool_state,
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::Coin2PC,
+            300,
+        )
+        .unwrap();
+        assert_eq!(quote.swap_fee, 1);
+        let quote = quote_base_in(
+            &pool_state,
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::Coin2PC,
+            10,
+        )
+        .unwrap();
+        assert_eq!(quote.swap_fee, 0);
+        let quote = quote_base_in(
+            &pool_state,
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::PC2Coin,
+            10,
+        )
+        .unwrap();
+        assert_eq!(quote.amount_out, 0);
+    }
+
+    #[test]
+    fn base_out() {
+        let quote = quote_base_out(
+            &pool_state(),
+            COIN_VAULT,
+            PC_VAULT,
+            SwapDirection::PC2Coin,
+            1_000_000,
+        )
+        .unwrap();
+        // ceil(50_000_000_000 * 1_000_000 / 999_000_000) = 50_050_051 before the fee
+        assert_eq!(quote.amount_in, 50_175_490);
+        assert_eq!(quote.swap_fee, 50_175_490 - 50_050_051);
+        assert_eq!(quote.amount_out, 1_000_000);
+    }
+
+    #[test]
+    fn base_out_rejects_more_than_the_vault() {
+        let pool_state = pool_state();
+        // the pnl owed is not part of the reserves
+        assert!(
+            quote_base_out(
+                &pool_state,
+                COIN_VAULT,
+                PC_VAULT,
+                SwapDirection::PC2Coin,
+                1_000_000_000
+            )
+            .is_err()
+        );
+        assert!(
+            quote_base_out(
+                &pool_state,
+                COIN_VAULT,
+                PC_VAULT,
+                SwapDirection::Coin2PC,
+                PC_VAULT
+            )
+            .is_err()
+        );
+        assert!(
+            quote_base_out(
+                &pool_state,
+                COIN_VAULT,
+                PC_VAULT,
+                SwapDirection::PC2Coin,
+                999_999_999
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::engine::swap::{SwapConfig, SwapQuote};
+
+/// A venue the bot can trade on. Implementations only load state and build
+/// instructions, sending is left to `engine::swap::execute_swap`.
+pub trait Dex {
+    type State;
+
+    /// fetch everything needed to quote and trade `mint` on this venue
+    fn load_state(&self, mint: &Pubkey) -> impl Future<Output = Result<Self::State>> + Send;
+
+    fn quote(&self, state: &Self::State, swap_config: &SwapConfig) -> Result<SwapQuote>;
+
+    fn build_swap_instructions(
+        &self,
+        state: &Self::State,
+        swap_config: &SwapConfig,
+    ) -> impl Future<Output = Result<Vec<Instruction>>> + Send;
+
+    /// static program and market accounts worth keeping in a lookup table
+    fn lookup_table_addresses(&self, _state: &Self::State) -> Vec<Pubkey> {
+        vec![]
+    }
+}
@@ -0,0 +1,117 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use log::info;
+use raydium_amm::accounts::AmmInfo;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::dex::{
+    pump_fun::{self, BondingCurveAccount, PUMP_PROGRAM},
+    pump_swap, raydium,
+};
+
+/// Where the liquidity for a mint currently lives, with the state loaded while resolving it
+#[derive(Clone, Debug)]
+pub enum Venue {
+    PumpFun {
+        mint: Pubkey,
+        bonding_curve: Pubkey,
+        bonding_curve_account: BondingCurveAccount,
+    },
+    PumpSwap {
+        mint: Pubkey,
+        pool_id: Pubkey,
+        pool: pump_swap::Pool,
+    },
+    RaydiumAmmV4 {
+        mint: Pubkey,
+        pool_id: Pubkey,
+        pool_state: AmmInfo,
+    },
+}
+
+impl Venue {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Venue::PumpFun { .. } => "pump.fun",
+            Venue::PumpSwap { .. } => "pump swap",
+            Venue::RaydiumAmmV4 { .. } => "raydium amm v4",
+        }
+    }
+
+    pub fn mint(&self) -> Pubkey {
+        match self {
+            Venue::PumpFun { mint, .. }
+            | Venue::PumpSwap { mint, .. }
+            | Venue::RaydiumAmmV4 { mint, .. } => *mint,
+        }
+    }
+}
+
+fn account_exists(
+    rpc_client: &solana_client::rpc_client::RpcClient,
+    address: &Pubkey,
+) -> Result<bool> {
+    Ok(rpc_client
+        .get_account_with_commitment(address, rpc_client.commitment())?
+        .value
+        .is_some())
+}
+
+/// Checks the bonding curve first, then the PumpSwap pool it migrates into,
+/// then falls back to a Raydium AMM v4 pool paired with wsol.
+pub async fn resolve_venue(
+    rpc_client: Arc<solana_client::rpc_client::RpcClient>,
+    mint: &str,
+) -> Result<Venue> {
+    let mint_pubkey = Pubkey::from_str(mint)?;
+    let pump_program = Pubkey::from_str(PUMP_PROGRAM)?;
+
+    // only a missing account moves on to the next venue, any other error is
+    // returned so a flaky rpc can't route a live token elsewhere
+    let bonding_curve = pump_fun::get_pda(&mint_pubkey, &pump_program)?;
+    if account_exists(&rpc_client, &bonding_curve)? {
+        let (bonding_curve, _, bonding_curve_account) =
+            pump_fun::get_bonding_curve_account(rpc_client.clone(), &mint_pubkey, &pump_program)
+                .await?;
+        if !bonding_curve_account.complete {
+            info!("[RESOLVE VENUE] {} => pump.fun", mint);
+            return Ok(Venue::PumpFun {
+                mint: mint_pubkey,
+                bonding_curve,
+                bonding_curve_account,
+            });
+        }
+        info!("[RESOLVE VENUE] {} bonding curve complete", mint);
+    } else {
+        info!("[RESOLVE VENUE] {} no bonding curve", mint);
+    }
+
+    let pool_id = pump_swap::get_canonical_pool_pda(&mint_pubkey)?;
+    if account_exists(&rpc_client, &pool_id)? {
+        let pool = pump_swap::get_pool(rpc_client.clone(), &pool_id).await?;
+        info!("[RESOLVE VENUE] {} => pump swap pool {}", mint, pool_id);
+        return Ok(Venue::PumpSwap {
+            mint: mint_pubkey,
+            pool_id,
+            pool,
+        });
+    }
+    info!("[RESOLVE VENUE] {} no pump swap pool", mint);
+
+    let (pool_id, pool_state) = raydium::get_pool_state_by_mint(rpc_client, mint)
+        .await
+        .map_err(|e| {
+            if e.to_string().starts_with("NotFoundPool") {
+                anyhow!("NotFoundVenue: no liquidity found for {}", mint)
+            } else {
+                e
+            }
+        })?;
+    info!("[RESOLVE VENUE] {} => raydium pool {}", mint, pool_id);
+    Ok(Venue::RaydiumAmmV4 {
+        mint: mint_pubkey,
+        pool_id,
+        pool_state,
+    })
+}
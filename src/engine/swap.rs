@@ -1,3 +1,24 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::{info, warn};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+
+use crate::{
+    core::{
+        alt,
+        tx::{self, SendChannel},
+    },
+    dex::{
+        pump_fun::Pump,
+        pump_swap::PumpSwap,
+        raydium::Raydium,
+        traits::Dex,
+        venue::{Venue, resolve_venue},
+    },
+};
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u64)]
 pub enum SwapDirection {
@@ -19,4 +40,107 @@ pub enum SwapInType {
 pub struct SwapConfig {
     pub(crate) slippage: u64,
     pub(crate) swap_direction: SwapDirection,
+    pub(crate) in_type: SwapInType,
+    /// ui amount of the input token for BaseIn, of the output token for BaseOut
+    pub(crate) amount: f64,
+    /// rpc, jito or nextblock
+    pub(crate) channel: SendChannel,
+    /// close the token account once a sell empties it, to get the rent back
+    pub(crate) close_token_account: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SwapQuote {
+    /// raw amount of the input token
+    pub amount_in: u64,
+    /// raw amount of the output token that actually arrives
+    pub amount_out: u64,
+    /// withheld by a token-2022 transfer fee on the traded mint, zero otherwise
+    pub transfer_fee: u64,
+    /// minimum out (base in) or maximum in (base out) after slippage
+    pub other_amount_threshold: u64,
+    pub price_impact: f64,
+}
+
+pub async fn execute_swap<D: Dex>(
+    dex: &D,
+    client: &RpcClient,
+    keypair: &Keypair,
+    mint: &Pubkey,
+    swap_config: &SwapConfig,
+) -> Result<Vec<String>> {
+    let state = dex.load_state(mint).await?;
+    let instructions = dex.build_swap_instructions(&state, swap_config).await?;
+    info!("swap instructions built: {}", instructions.len());
+    let lookup_tables = alt::get_lookup_table_manager()
+        .tables_for(client, &dex.lookup_table_addresses(&state))
+        .await
+        .unwrap_or_else(|err| {
+            warn!("lookup tables not loaded, sending without: {}", err);
+            vec![]
+        });
+    tx::new_signed_and_send(
+        client,
+        keypair,
+        instructions,
+        lookup_tables,
+        swap_config.channel,
+        None,
+    )
+    .await
+}
+
+/// swap `mint` wherever its liquidity currently lives, see `resolve_venue`
+pub async fn swap_mint(
+    rpc_nonblocking_client: Arc<solana_client::nonblocking::rpc_client::RpcClient>,
+    rpc_client: Arc<RpcClient>,
+    keypair: Arc<Keypair>,
+    mint: &str,
+    swap_config: SwapConfig,
+) -> Result<Vec<String>> {
+    let venue = resolve_venue(rpc_client.clone(), mint).await?;
+    info!("swapping {} on {}", mint, venue.name());
+    match venue {
+        Venue::PumpFun { .. } => {
+            Pump::new(rpc_nonblocking_client, rpc_client, keypair)
+                .swap(mint, swap_config)
+                .await
+        }
+        Venue::PumpSwap { .. } => {
+            PumpSwap::new(rpc_nonblocking_client, rpc_client, keypair)
+                .swap(mint, swap_config)
+                .await
+        }
+        Venue::RaydiumAmmV4 { pool_id, .. } => {
+            Raydium {
+                pool_id: Some(pool_id.to_string()),
+                ..Raydium::new(rpc_nonblocking_client, rpc_client, keypair)
+            }
+            .swap(mint, swap_config)
+            .await
+        }
+    }
+}
+
+/// store the venue's static accounts for `mint` in the lookup table `name`,
+/// so later swaps on it compile against the table
+pub async fn prepare_lookup_table<D: Dex>(
+    dex: &D,
+    client: &RpcClient,
+    keypair: &Keypair,
+    mint: &Pubkey,
+    name: &str,
+) -> Result<()> {
+    let state = dex.load_state(mint).await?;
+    let addresses = dex.lookup_table_addresses(&state);
+    let table = alt::get_lookup_table_manager()
+        .ensure_table(client, keypair, name, &addresses)
+        .await?;
+    info!(
+        "lookup table {}({}) holds {} addresses",
+        name,
+        table.key,
+        table.addresses.len()
+    );
+    Ok(())
 }
@@ -20,6 +20,16 @@ async fn main() {
         return;
     }
 
+    // connect to the tip stream now rather than on the first trade
+    if service::tip_stream::get_tip_floor_subscriber().is_some() {
+        info!("jito tip stream subscriber started");
+    }
+
+    // start refreshing the blockhash now so the first trade doesn't wait on it
+    if core::blockhash::get_blockhash_cache().is_some() {
+        info!("blockhash cache started");
+    }
+
     // utils::jjj::import_env_var();
 
     // info!("{}","kkkk");
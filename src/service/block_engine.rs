@@ -0,0 +1,427 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures_util::future::join_all;
+use log::{info, warn};
+use serde_json::json;
+use solana_sdk::transaction::VersionedTransaction;
+use tokio::time::{Instant, sleep};
+
+use crate::{
+    service::jito::{self, JitoClient},
+    utils::jjj::{import_env_var_with_default, import_env_var_with_option},
+};
+
+// https://docs.jito.wtf/lowlatencytxnsend/#api
+pub const JITO_BLOCK_ENGINES: [(&str, &str); 7] = [
+    (
+        "amsterdam",
+        "https://amsterdam.mainnet.block-engine.jito.wtf",
+    ),
+    ("dublin", "https://dublin.mainnet.block-engine.jito.wtf"),
+    (
+        "frankfurt",
+        "https://frankfurt.mainnet.block-engine.jito.wtf",
+    ),
+    ("london", "https://london.mainnet.block-engine.jito.wtf"),
+    ("ny", "https://ny.mainnet.block-engine.jito.wtf"),
+    ("slc", "https://slc.mainnet.block-engine.jito.wtf"),
+    ("tokyo", "https://tokyo.mainnet.block-engine.jito.wtf"),
+];
+
+static BLOCK_ENGINES: LazyLock<Option<Arc<BlockEngineSet>>> = LazyLock::new(|| {
+    let engines = import_env_var_with_option("JITO_BLOCK_ENGINES")?
+        .into_string()
+        .ok()?;
+    let engines = parse_engines(&engines);
+    if engines.is_empty() {
+        warn!("JITO_BLOCK_ENGINES has no usable engine");
+        return None;
+    }
+    let set = Arc::new(BlockEngineSet::new(
+        engines,
+        import_env_var_with_default("JITO_FANOUT", 3),
+        Duration::from_millis(import_env_var_with_default("JITO_PROBE_TIMEOUT_MS", 2000)),
+    ));
+    // without a runtime the engines are used unprobed, in the configured order
+    if tokio::runtime::Handle::try_current().is_ok() {
+        set.clone()
+            .spawn_prober(Duration::from_secs(import_env_var_with_default(
+                "JITO_PROBE_INTERVAL_SECS",
+                30,
+            )));
+    }
+    Some(set)
+});
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEngine {
+    pub region: String,
+    /// base url, without /api/v1
+    pub url: String,
+}
+
+impl BlockEngine {
+    pub fn new(region: &str, url: &str) -> Self {
+        Self {
+            region: region.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn client(&self, uuid_string: Option<String>) -> JitoClient {
+        JitoClient::new(&format!("{}/api/v1", self.url), uuid_string)
+    }
+}
+
+/// "all" for the built-in list, else comma separated region names from that
+/// list or region=url pairs, e.g. "ny,tokyo,local=http://127.0.0.1:8080"
+pub fn parse_engines(value: &str) -> Vec<BlockEngine> {
+    if value.trim() == "all" {
+        return JITO_BLOCK_ENGINES
+            .iter()
+            .map(|(region, url)| BlockEngine::new(region, url))
+            .collect();
+    }
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((region, url)) => Some(BlockEngine::new(region.trim(), url.trim())),
+            None => match JITO_BLOCK_ENGINES
+                .iter()
+                .find(|(region, _)| *region == entry)
+            {
+                Some((region, url)) => Some(BlockEngine::new(region, url)),
+                None => {
+                    warn!("unknown jito region: {}", entry);
+                    None
+                }
+            },
+        })
+        .collect()
+}
+
+/// Sends and outcomes of one region since start. landings aren't counted:
+/// every region returns the same bundle id, so a landed bundle can't be
+/// traced to the region that forwarded it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegionStats {
+    pub sent: u64,
+    pub accepted: u64,
+    /// last probe round trip, None when the last probe failed
+    pub latency: Option<Duration>,
+}
+
+/// Answer of every region a bundle was sent to.
+#[derive(Debug)]
+pub struct FanOutResult {
+    /// bundle ids are derived from the signatures, so every region returns the same
+    pub bundle_id: String,
+    /// regions that accepted it, fastest answer first
+    pub accepted: Vec<(BlockEngine, Duration)>,
+    pub rejected: Vec<(BlockEngine, anyhow::Error)>,
+}
+
+/// A set of regional block engines, ranked by the round trip of a periodic
+/// probe. Each bundle goes to the fastest `fanout` of them at once.
+pub struct BlockEngineSet {
+    engines: Vec<BlockEngine>,
+    fanout: usize,
+    probe_timeout: Duration,
+    http: reqwest::Client,
+    stats: Mutex<HashMap<String, RegionStats>>,
+    // None until the first probe, so unprobed engines keep the configured order
+    ranking: RwLock<Option<Vec<BlockEngine>>>,
+}
+
+impl BlockEngineSet {
+    pub fn new(engines: Vec<BlockEngine>, fanout: usize, probe_timeout: Duration) -> Self {
+        Self {
+            engines,
+            fanout: fanout.max(1),
+            probe_timeout,
+            http: reqwest::Client::new(),
+            stats: Mutex::new(HashMap::new()),
+            ranking: RwLock::new(None),
+        }
+    }
+
+    pub fn engines(&self) -> &[BlockEngine] {
+        &self.engines
+    }
+
+    pub fn spawn_prober(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            loop {
+                self.probe().await;
+                sleep(interval).await;
+            }
+        });
+    }
+
+    /// time a getTipAccounts call against every engine and rank them,
+    /// engines that fail or time out go last
+    pub async fn probe(&self) -> Vec<(BlockEngine, Option<Duration>)> {
+        let mut results = join_all(self.engines.iter().map(|engine| async move {
+            let latency = self
+                .probe_engine(engine)
+                .await
+                .inspect_err(|err| warn!("jito probe {} failed: {}", engine.region, err))
+                .ok();
+            (engine.clone(), latency)
+        }))
+        .await;
+        results.sort_by_key(|(_, latency)| latency.unwrap_or(Duration::MAX));
+
+        let mut stats = self.stats.lock().unwrap();
+        for (engine, latency) in results.iter() {
+            stats.entry(engine.region.clone()).or_default().latency = *latency;
+        }
+        drop(stats);
+        info!(
+            "jito engines by latency: {:?}",
+            results
+                .iter()
+                .map(|(engine, latency)| (engine.region.as_str(), *latency))
+                .collect::<Vec<_>>()
+        );
+        *self.ranking.write().unwrap() =
+            Some(results.iter().map(|(engine, _)| engine.clone()).collect());
+        results
+    }
+
+    async fn probe_engine(&self, engine: &BlockEngine) -> Result<Duration> {
+        let start_time = Instant::now();
+        let response = self
+            .http
+            .post(format!("{}/api/v1/bundles", engine.url))
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "getTipAccounts", "params": []}))
+            .timeout(self.probe_timeout)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("status {}", response.status()));
+        }
+        Ok(start_time.elapsed())
+    }
+
+    /// the `fanout` best ranked engines
+    pub fn fastest(&self) -> Vec<BlockEngine> {
+        let ranking = self.ranking.read().unwrap();
+        ranking
+            .as_ref()
+            .unwrap_or(&self.engines)
+            .iter()
+            .take(self.fanout)
+            .cloned()
+            .collect()
+    }
+
+    /// send the bundle to the fastest engines concurrently, Ok as long as
+    /// one of them accepted it
+    pub async fn send_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+        uuid_string: Option<&str>,
+    ) -> Result<FanOutResult> {
+        let engines = self.fastest();
+        let results = join_all(engines.into_iter().map(|engine| async move {
+            let start_time = Instant::now();
+            let jito_client = engine.client(uuid_string.map(String::from));
+            let result = jito::send_bundle(&jito_client, transactions, uuid_string).await;
+            (engine, result, start_time.elapsed())
+        }))
+        .await;
+
+        let mut bundle_id = None;
+        let mut accepted = vec![];
+        let mut rejected = vec![];
+        let mut stats = self.stats.lock().unwrap();
+        for (engine, result, elapsed) in results {
+            let region_stats = stats.entry(engine.region.clone()).or_default();
+            region_stats.sent += 1;
+            match result {
+                Ok(id) => {
+                    info!(
+                        "bundle {} accepted by {} in {:?}",
+                        id, engine.region, elapsed
+                    );
+                    region_stats.accepted += 1;
+                    bundle_id.get_or_insert(id);
+                    accepted.push((engine, elapsed));
+                }
+                Err(err) => {
+                    warn!("bundle rejected by {}: {}", engine.region, err);
+                    rejected.push((engine, err));
+                }
+            }
+        }
+        drop(stats);
+        accepted.sort_by_key(|(_, elapsed)| *elapsed);
+        let bundle_id = bundle_id.ok_or_else(|| {
+            anyhow!(
+                "every jito region rejected the bundle: {:?}",
+                rejected
+                    .iter()
+                    .map(|(engine, err)| format!("{}: {}", engine.region, err))
+                    .collect::<Vec<_>>()
+            )
+        })?;
+        Ok(FanOutResult {
+            bundle_id,
+            accepted,
+            rejected,
+        })
+    }
+
+    pub fn stats(&self) -> HashMap<String, RegionStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+/// process wide engine set, None unless JITO_BLOCK_ENGINES is set,
+/// see also JITO_FANOUT, JITO_PROBE_INTERVAL_SECS and JITO_PROBE_TIMEOUT_MS
+pub fn get_block_engines() -> Option<Arc<BlockEngineSet>> {
+    BLOCK_ENGINES.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine as _, engine::general_purpose::STANDARD};
+
+    use super::*;
+    use crate::utils::http_stub::HttpStub;
+
+    const ACCEPTED: &str = r#"{"jsonrpc":"2.0","result":"bundle-1","id":1}"#;
+    const REJECTED: &str =
+        r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"bundle already processed"},"id":1}"#;
+
+    fn engine_set(
+        stubs: &[(&str, &HttpStub)],
+        fanout: usize,
+        probe_timeout: Duration,
+    ) -> BlockEngineSet {
+        let engines = stubs
+            .iter()
+            .map(|(region, stub)| BlockEngine::new(region, &stub.url))
+            .collect();
+        BlockEngineSet::new(engines, fanout, probe_timeout)
+    }
+
+    fn regions(engines: &[BlockEngine]) -> Vec<&str> {
+        engines
+            .iter()
+            .map(|engine| engine.region.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_regions_and_urls() {
+        let engines = parse_engines("ny, tokyo,local=http://127.0.0.1:8080/,nowhere");
+        assert_eq!(regions(&engines), ["ny", "tokyo", "local"]);
+        assert_eq!(engines[2].url, "http://127.0.0.1:8080");
+        assert_eq!(parse_engines("all").len(), JITO_BLOCK_ENGINES.len());
+    }
+
+    #[tokio::test]
+    async fn probe_ranks_by_latency() {
+        let slow = HttpStub::start(200, ACCEPTED, Duration::from_millis(300)).await;
+        let down = HttpStub::start(503, "{}", Duration::ZERO).await;
+        let hanging = HttpStub::start(200, ACCEPTED, Duration::from_secs(5)).await;
+        let fast = HttpStub::start(200, ACCEPTED, Duration::ZERO).await;
+        let set = engine_set(
+            &[
+                ("slow", &slow),
+                ("down", &down),
+                ("hanging", &hanging),
+                ("fast", &fast),
+            ],
+            2,
+            Duration::from_secs(1),
+        );
+        // unprobed engines keep the configured order
+        assert_eq!(regions(&set.fastest()), ["slow", "down"]);
+
+        let results = set.probe().await;
+        assert_eq!(regions(&set.fastest()), ["fast", "slow"]);
+        assert_eq!(results[0].0.region, "fast");
+        assert_eq!(results[1].0.region, "slow");
+        assert!(results[0].1.unwrap() < results[1].1.unwrap());
+        // failures and timeouts go last without a latency
+        assert!(results[2..].iter().all(|(_, latency)| latency.is_none()));
+        assert_eq!(set.stats()["down"].latency, None);
+        assert!(set.stats()["fast"].latency.is_some());
+
+        let request = &fast.requests()[0];
+        assert_eq!(request.path, "/api/v1/bundles");
+        assert_eq!(request.json()["method"], "getTipAccounts");
+    }
+
+    #[tokio::test]
+    async fn send_bundle_fans_out() {
+        let first = HttpStub::start(200, ACCEPTED, Duration::ZERO).await;
+        let second = HttpStub::start(200, REJECTED, Duration::ZERO).await;
+        let third = HttpStub::start(200, ACCEPTED, Duration::from_millis(200)).await;
+        let unused = HttpStub::start(200, ACCEPTED, Duration::ZERO).await;
+        let set = engine_set(
+            &[
+                ("first", &first),
+                ("second", &second),
+                ("third", &third),
+                ("unused", &unused),
+            ],
+            3,
+            Duration::from_secs(1),
+        );
+        let txn = VersionedTransaction::default();
+
+        let result = set
+            .send_bundle(std::slice::from_ref(&txn), None)
+            .await
+            .unwrap();
+        assert_eq!(result.bundle_id, "bundle-1");
+        let accepted: Vec<_> = result
+            .accepted
+            .iter()
+            .map(|(engine, _)| engine.region.as_str())
+            .collect();
+        assert_eq!(accepted, ["first", "third"]);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].0.region, "second");
+
+        // every engine within the fanout got the same bundle, the rest nothing
+        for stub in [&first, &second, &third] {
+            let requests = stub.requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].path, "/api/v1/bundles");
+            let body = requests[0].json();
+            assert_eq!(body["method"], "sendBundle");
+            assert_eq!(
+                body["params"][0][0],
+                STANDARD.encode(bincode::serialize(&txn).unwrap())
+            );
+        }
+        assert!(unused.requests().is_empty());
+
+        let stats = set.stats();
+        assert_eq!((stats["first"].sent, stats["first"].accepted), (1, 1));
+        assert_eq!((stats["second"].sent, stats["second"].accepted), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn send_bundle_fails_when_every_engine_rejects() {
+        let rejecting = HttpStub::start(200, REJECTED, Duration::ZERO).await;
+        let set = engine_set(&[("rejecting", &rejecting)], 3, Duration::from_secs(1));
+        let err = set
+            .send_bundle(&[VersionedTransaction::default()], None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("rejecting"), "{}", err);
+    }
+}
@@ -1,120 +1,568 @@
-use crate::utils::jjj::{import_env_var, import_env_var_with_default};
+use crate::service::rate_limit::{ThrottleStats, backoff_with_jitter, get_jito_limiter};
+use crate::service::tip_stream::{clamp_tip, get_tip_from_floor};
+use crate::utils::jjj::{import_env_var, import_env_var_with_default, round_robin};
 use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
 use jito_sdk_rust::JitoJsonRpcSDK;
 use log::{error, info, warn};
-use serde_json::Value;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use solana_program::system_instruction;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::VersionedTransaction};
+use spl_token::ui_amount_to_amount;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::{LazyLock, RwLock};
 use tokio::time::Instant;
 use tokio::time::{Duration, sleep};
 
-pub fn get_jito_sdk(uuid_string: Option<String>) -> JitoJsonRpcSDK {
+// https://docs.jito.wtf/lowlatencytxnsend/#tip-amount, used until the
+// block engine answers getTipAccounts
+pub const JITO_TIP_ACCOUNTS: [&str; 8] = [
+    "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5",
+    "HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe",
+    "Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY",
+    "ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49",
+    "DfXygSm4jCyNCybVYYK6DwvWqjKee8pbDmJGcLWNDXjh",
+    "ADuUkR4vqLUMWXxW9gh6D6L8pMSawimctcNZ5pGwDcEt",
+    "DttWaMuVvTiduZRnguLF7jNxTgiMBZ1hyAumKUiL2KRL",
+    "3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT",
+];
+
+// json-rpc error code of a block engine 429
+const JITO_RATE_LIMITED: i64 = -32097;
+
+static TIP_ACCOUNTS: LazyLock<RwLock<Option<(Vec<Pubkey>, Instant)>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// How a jito trade is submitted and where its tip goes.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum JitoMode {
+    /// swap tx plus a separate tip tx in one bundle
+    #[default]
+    Bundle,
+    /// tip as the last instruction of the swap tx, sent as a one tx bundle
+    BundleTipInTx,
+    /// tip as the last instruction, sent through jito's sendTransaction
+    Transaction,
+}
+
+impl JitoMode {
+    pub fn tip_in_tx(&self) -> bool {
+        *self != JitoMode::Bundle
+    }
+}
+
+/// Which rate limit bucket a block engine request takes its token from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum JitoRequest {
+    /// sendBundle and sendTransaction
+    Send,
+    /// getBundleStatuses and getInflightBundleStatuses
+    Status,
+    /// getTipAccounts, not tied to a uuid
+    TipAccounts,
+}
+
+/// The sdk client plus the engine url and uuid it talks to, which together
+/// pick the rate limit bucket of each request.
+pub struct JitoClient {
+    sdk: JitoJsonRpcSDK,
+    base_url: String,
+    uuid_string: Option<String>,
+}
+
+impl JitoClient {
+    /// `base_url` ends in /api/v1
+    pub fn new(base_url: &str, uuid_string: Option<String>) -> Self {
+        Self {
+            sdk: JitoJsonRpcSDK::new(base_url, uuid_string.clone()),
+            base_url: base_url.to_string(),
+            uuid_string,
+        }
+    }
+
+    /// jito limits each uuid per engine, sends and status polls are kept
+    /// apart so polling can't starve sending. tip accounts only count
+    /// against the ip.
+    pub fn rate_limit_key(&self, request: JitoRequest) -> String {
+        let uuid = self.uuid_string.as_deref().unwrap_or("no uuid");
+        match request {
+            JitoRequest::Send => format!("{} {}", self.base_url, uuid),
+            JitoRequest::Status => format!("{} {} status", self.base_url, uuid),
+            JitoRequest::TipAccounts => format!("{} tip accounts", self.base_url),
+        }
+    }
+}
+
+impl Deref for JitoClient {
+    type Target = JitoJsonRpcSDK;
+
+    fn deref(&self) -> &JitoJsonRpcSDK {
+        &self.sdk
+    }
+}
+
+pub fn get_jito_sdk(uuid_string: Option<String>) -> JitoClient {
     // "https://mainnet.block-engine.jito.wtf/api/v1"
     let base_api_url = import_env_var("JITO_BLOCK_ENGINE_URL") + "/api/v1";
-    JitoJsonRpcSDK::new(&base_api_url, uuid_string)
+    JitoClient::new(&base_api_url, uuid_string)
 }
 
 pub fn get_tip_value() -> f64 {
-    // jito tip, the default limit is 0.1
-    import_env_var_with_default("JITO_TIP_VALUE", 0.1)
+    // follow the live tip floor, fall back to the fixed tip, both within
+    // JITO_TIP_MIN..=JITO_TIP_MAX
+    get_tip_from_floor()
+        .unwrap_or_else(|| clamp_tip(import_env_var_with_default("JITO_TIP_VALUE", 0.1)))
 }
 
-pub async fn wait_for_bundle_confirmation<F, Fut>(
-    fn1: F,
-    bundle_id: String,
-    period: Duration,
-    period_time: Duration,
-) -> Result<Vec<String>>
+/// the tip account list, fetched at most once per JITO_TIP_ACCOUNTS_TTL_SECS.
+/// a failed fetch keeps the previous list, or the known accounts at start.
+pub async fn get_tip_accounts(jito_client: &JitoClient) -> Vec<Pubkey> {
+    let ttl = Duration::from_secs(import_env_var_with_default(
+        "JITO_TIP_ACCOUNTS_TTL_SECS",
+        3600,
+    ));
+    if let Some((accounts, fetched_at)) = TIP_ACCOUNTS.read().unwrap().as_ref() {
+        if fetched_at.elapsed() < ttl {
+            return accounts.clone();
+        }
+    }
+    let response = with_rate_limit(
+        &jito_client.rate_limit_key(JitoRequest::TipAccounts),
+        || jito_client.get_tip_accounts(),
+    )
+    .await;
+    let accounts: Vec<Pubkey> = response
+        .inspect_err(|err| warn!("tip account fetch failed: {}", err))
+        .ok()
+        .and_then(|response| {
+            response["result"].as_array().map(|accounts| {
+                accounts
+                    .iter()
+                    .filter_map(|account| Pubkey::from_str(account.as_str()?).ok())
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+    let mut cache = TIP_ACCOUNTS.write().unwrap();
+    if !accounts.is_empty() {
+        *cache = Some((accounts.clone(), Instant::now()));
+        return accounts;
+    }
+    match cache.as_ref() {
+        Some((accounts, _)) => accounts.clone(),
+        None => JITO_TIP_ACCOUNTS
+            .iter()
+            .map(|account| Pubkey::from_str(account).unwrap())
+            .collect(),
+    }
+}
+
+pub async fn pick_tip_account(jito_client: &JitoClient) -> Pubkey {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    // never empty, the known accounts stand in for a failed fetch
+    round_robin(&NEXT, &get_tip_accounts(jito_client).await).unwrap()
+}
+
+/// transfer of the current tip value to one of the jito tip accounts
+pub async fn get_tip_instruction(jito_client: &JitoClient, payer: &Pubkey) -> Result<Instruction> {
+    let tip_account = pick_tip_account(jito_client).await;
+    let tip = get_tip_value();
+    let tip_lamports = ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS);
+    info!(
+        "tip account: {}, tip(sol): {}, lamports: {}",
+        tip_account, tip, tip_lamports
+    );
+    Ok(system_instruction::transfer(
+        payer,
+        &tip_account,
+        tip_lamports,
+    ))
+}
+
+/// base64 wire format expected by the block engine
+pub fn encode_transaction(txn: &VersionedTransaction) -> Result<String> {
+    Ok(STANDARD.encode(bincode::serialize(txn)?))
+}
+
+pub async fn send_bundle(
+    jito_client: &JitoClient,
+    transactions: &[VersionedTransaction],
+    uuid_string: Option<&str>,
+) -> Result<String> {
+    let encoded = transactions
+        .iter()
+        .map(encode_transaction)
+        .collect::<Result<Vec<_>>>()?;
+    let params = json!([encoded, {"encoding": "base64"}]);
+    let response = with_rate_limit(&jito_client.rate_limit_key(JitoRequest::Send), || {
+        jito_client.send_bundle(Some(params.clone()), uuid_string)
+    })
+    .await?;
+    response["result"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("send_bundle failed: {}", response))
+}
+
+/// single transaction through the block engine's sendTransaction endpoint
+pub async fn send_transaction(
+    jito_client: &JitoClient,
+    txn: &VersionedTransaction,
+) -> Result<String> {
+    let params = json!({"tx": encode_transaction(txn)?, "skipPreflight": true});
+    let response = with_rate_limit(&jito_client.rate_limit_key(JitoRequest::Send), || {
+        jito_client.send_txn(Some(params.clone()), false)
+    })
+    .await?;
+    response["result"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow!("send_transaction failed: {}", response))
+}
+
+/// run a block engine call under the rate limit of `key`, see
+/// `JitoClient::rate_limit_key`, and retry it with backoff while it is
+/// answered with 429, up to JITO_429_RETRIES times
+pub async fn with_rate_limit<F, Fut, E>(key: &str, call: F) -> Result<Value>
 where
-    F: Fn(String) -> Fut,
-    Fut: Future<Output = Result<Value>>,
+    F: Fn() -> Fut,
+    Fut: Future<Output = std::result::Result<Value, E>>,
+    E: Into<anyhow::Error>,
 {
+    let limiter = get_jito_limiter();
+    let retries: u32 = import_env_var_with_default("JITO_429_RETRIES", 3);
+    let backoff = Duration::from_millis(import_env_var_with_default("JITO_429_BACKOFF_MS", 200));
+    let mut attempt = 0;
+    loop {
+        limiter.acquire(key).await;
+        let response = call().await.map_err(Into::into);
+        let rate_limited = match &response {
+            Ok(response) => is_rate_limited(response),
+            Err(err) => is_rate_limited_error(err),
+        };
+        if !rate_limited {
+            return response;
+        }
+        limiter.update(key, |stats| stats.rate_limited += 1);
+        if attempt >= retries {
+            limiter.update(key, |stats| stats.gave_up += 1);
+            return Err(anyhow!(
+                "jito rate limited after {} retries: {:?}",
+                retries,
+                response
+            ));
+        }
+        let wait = backoff_with_jitter(backoff, attempt);
+        attempt += 1;
+        limiter.update(key, |stats| stats.retries += 1);
+        warn!(
+            "jito rate limited, retrying in {:?} ({}/{})",
+            wait, attempt, retries
+        );
+        sleep(wait).await;
+    }
+}
+
+// the block engine answers 429 with a json-rpc error body, which the sdk
+// hands back as a successful response
+fn is_rate_limited(response: &Value) -> bool {
+    response["error"]["code"].as_i64() == Some(JITO_RATE_LIMITED)
+}
+
+// a 429 that failed before its body was read
+fn is_rate_limited_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|err| err.status())
+        == Some(StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// jito throttling per engine, uuid and request kind since start
+pub fn get_throttle_stats() -> HashMap<String, ThrottleStats> {
+    get_jito_limiter().stats()
+}
+
+/// Where a bundle ended up, as far as the block engine can tell.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BundleOutcome {
+    /// `slot` is None when the block engine left out `landed_slot`
+    Landed { slot: Option<u64> },
+    /// every region rejected it, e.g. a failing transaction or a low tip
+    Failed,
+    /// the block engine never saw it
+    Invalid,
+    /// accepted, then aged out without landing
+    Dropped,
+    /// still pending when the wait ran out
+    Timeout,
+}
+
+impl BundleOutcome {
+    /// outcomes where sending the same bundle again can still land it
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, BundleOutcome::Invalid | BundleOutcome::Dropped)
+    }
+}
+
+/// poll getInflightBundleStatuses every `period` until the bundle reaches a
+/// terminal state or `period_time` runs out
+pub async fn wait_for_bundle_confirmation(
+    jito_client: &JitoClient,
+    bundle_id: &str,
+    period: Duration,
+    period_time: Duration,
+) -> BundleOutcome {
     let start_time = Instant::now();
+    let mut pending = false;
+    let mut invalid_count = 0;
     while start_time.elapsed() < period_time {
-        if let Ok(res) = fn1(bundle_id.clone()).await {
-            let bundle_status = get_bundle_status(&res)?;
-            match bundle_status.confirmation_status.as_deref() {
-                Some("confirmed") => {
-                    info!("Bundle confirmed on-chain. Waiting for finalization...");
-                    check_transaction_error(&bundle_status)?;
-                }
-                Some("finalized") => {
-                    info!("Bundle finalized on-chain successfully!");
-                    check_transaction_error(&bundle_status)?;
-                    print_transaction_url(&bundle_status);
-                    return Ok(bundle_status.transactions.unwrap_or_default());
-                }
-                Some(status) => {
-                    warn!(
-                        "Unexpected final bundle status: {}. Continuing to poll...",
-                        status
-                    );
-                }
-                None => {
-                    warn!("Unable to parse final bundle status. Continuing to poll...");
+        let status = match with_rate_limit(&jito_client.rate_limit_key(JitoRequest::Status), || {
+            jito_client.get_in_flight_bundle_statuses(vec![bundle_id.to_string()])
+        })
+        .await
+        {
+            Ok(response) => get_inflight_status(&response),
+            Err(err) => {
+                warn!("Error fetching bundle status: {:?}", err);
+                None
+            }
+        };
+        match status {
+            Some((InflightStatus::Landed, slot)) => {
+                info!("Bundle {} landed in slot {:?}", bundle_id, slot);
+                return BundleOutcome::Landed { slot };
+            }
+            Some((InflightStatus::Failed, _)) => {
+                error!("Bundle {} failed", bundle_id);
+                return BundleOutcome::Failed;
+            }
+            Some((InflightStatus::Pending, _)) => pending = true,
+            Some((InflightStatus::Invalid, _)) if pending => {
+                warn!("Bundle {} dropped", bundle_id);
+                return BundleOutcome::Dropped;
+            }
+            Some((InflightStatus::Invalid, _)) => {
+                // a new bundle may not be indexed yet, only trust a repeated invalid
+                invalid_count += 1;
+                if invalid_count >= 2 {
+                    warn!("Bundle {} invalid", bundle_id);
+                    return BundleOutcome::Invalid;
                 }
             }
-
-            sleep(period).await;
+            None => {}
         }
+        sleep(period).await;
     }
-    Err(anyhow!(
-        "Failed to get finalized status after {} secs",
+    warn!(
+        "Bundle {} still pending after {} secs",
+        bundle_id,
         period_time.as_secs()
-    ))
+    );
+    BundleOutcome::Timeout
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum InflightStatus {
+    Pending,
+    Landed,
+    Failed,
+    Invalid,
 }
 
-fn get_bundle_status(status_response: &Value) -> Result<BundleStatus> {
-    status_response
+fn get_inflight_status(status_response: &Value) -> Option<(InflightStatus, Option<u64>)> {
+    let bundle_status = status_response
         .get("result")
         .and_then(|result| result.get("value"))
         .and_then(|value| value.as_array())
-        .and_then(|statuses| statuses.first())
-        .ok_or_else(|| anyhow!("Failed to parse bundle status"))
-        .map(|bundle_status| BundleStatus {
-            confirmation_status: bundle_status
-                .get("confirmation_status")
-                .and_then(|s| s.as_str())
-                .map(String::from),
-            err: bundle_status.get("err").cloned(),
-            transactions: bundle_status
-                .get("transactions")
-                .and_then(|t| t.as_array())
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(String::from))
-                        .collect()
-                }),
-        })
+        .and_then(|statuses| statuses.first())?;
+    let status = match bundle_status.get("status").and_then(|s| s.as_str())? {
+        "Pending" => InflightStatus::Pending,
+        "Landed" => InflightStatus::Landed,
+        "Failed" => InflightStatus::Failed,
+        "Invalid" => InflightStatus::Invalid,
+        status => {
+            warn!("Unexpected bundle status: {}", status);
+            return None;
+        }
+    };
+    let landed_slot = bundle_status.get("landed_slot").and_then(|s| s.as_u64());
+    Some((status, landed_slot))
 }
 
-fn check_transaction_error(bundle_status: &BundleStatus) -> Result<()> {
-    if let Some(err) = &bundle_status.err {
-        if err["Ok"].is_null() {
-            info!("Transaction executed without errors.");
-            Ok(())
-        } else {
-            error!("Transaction encountered an error: {:?}", err);
-            Err(anyhow!("Transaction encountered an error"))
-        }
-    } else {
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::http_stub::HttpStub;
+
+    const PENDING: &str = r#"{"jsonrpc":"2.0","result":{"context":{"slot":280999028},"value":[{"bundle_id":"b1","status":"Pending","landed_slot":null}]},"id":1}"#;
+    const LANDED: &str = r#"{"jsonrpc":"2.0","result":{"context":{"slot":280999030},"value":[{"bundle_id":"b1","status":"Landed","landed_slot":280999029}]},"id":1}"#;
+    const LANDED_NO_SLOT: &str = r#"{"jsonrpc":"2.0","result":{"context":{"slot":280999030},"value":[{"bundle_id":"b1","status":"Landed","landed_slot":null}]},"id":1}"#;
+    const FAILED: &str = r#"{"jsonrpc":"2.0","result":{"context":{"slot":280999030},"value":[{"bundle_id":"b1","status":"Failed","landed_slot":null}]},"id":1}"#;
+    const INVALID: &str = r#"{"jsonrpc":"2.0","result":{"context":{"slot":280999030},"value":[{"bundle_id":"b1","status":"Invalid","landed_slot":null}]},"id":1}"#;
+
+    // poll a stub answering `bodies` in turn until the bundle settles
+    async fn wait_on(bodies: &[&str], period_time: Duration) -> (BundleOutcome, usize) {
+        let stub = HttpStub::start_sequence(200, bodies, Duration::ZERO).await;
+        let jito_client = JitoClient::new(&stub.url, None);
+        let outcome = wait_for_bundle_confirmation(
+            &jito_client,
+            "b1",
+            Duration::from_millis(10),
+            period_time,
+        )
+        .await;
+        (outcome, stub.requests().len())
     }
-}
 
-fn print_transaction_url(bundle_status: &BundleStatus) {
-    if let Some(transactions) = &bundle_status.transactions {
-        if let Some(tx_id) = transactions.first() {
-            info!("Transaction URL: https://solscan.io/tx/{}", tx_id);
-        } else {
-            warn!("Unable to extract transaction ID.");
-        }
-    } else {
-        warn!("No transactions found in the bundle status.");
+    #[test]
+    fn rate_limits_are_told_by_code_and_status() {
+        let limited = json!({"jsonrpc":"2.0","error":{"code":-32097,"message":"Rate limit exceeded. Limit: 1 per second for txn requests"},"id":1});
+        assert!(is_rate_limited(&limited));
+        // a message mentioning 429 is not a rate limit
+        let rejected = json!({"jsonrpc":"2.0","error":{"code":-32602,"message":"bundle contains tx 4293 that already landed"},"id":1});
+        assert!(!is_rate_limited(&rejected));
+        assert!(!is_rate_limited(
+            &json!({"jsonrpc":"2.0","result":"b1","id":1})
+        ));
+        assert!(!is_rate_limited_error(&anyhow!("tip 0.00429 too low")));
+    }
+
+    #[tokio::test]
+    async fn http_429_is_a_rate_limit() {
+        let status_error = |status: u16| async move {
+            let stub = HttpStub::start(status, "{}", Duration::ZERO).await;
+            let err = reqwest::get(&stub.url)
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap_err();
+            anyhow::Error::from(err)
+        };
+        assert!(is_rate_limited_error(&status_error(429).await));
+        assert!(!is_rate_limited_error(&status_error(503).await));
     }
-}
 
-#[derive(Debug)]
-struct BundleStatus {
-    confirmation_status: Option<String>,
-    err: Option<Value>,
-    transactions: Option<Vec<String>>,
+    #[tokio::test]
+    async fn rate_limited_calls_are_retried() {
+        let stub = HttpStub::start(
+            429,
+            r#"{"jsonrpc":"2.0","error":{"code":-32097,"message":"Rate limit exceeded"},"id":1}"#,
+            Duration::ZERO,
+        )
+        .await;
+        let jito_client = JitoClient::new(&stub.url, None);
+        let key = jito_client.rate_limit_key(JitoRequest::TipAccounts);
+        let result = with_rate_limit(&key, || jito_client.get_tip_accounts()).await;
+
+        assert!(result.is_err());
+        let retries: usize = import_env_var_with_default("JITO_429_RETRIES", 3);
+        assert_eq!(stub.requests().len(), retries + 1);
+        let stats = &get_throttle_stats()[&key];
+        assert_eq!(stats.rate_limited as usize, retries + 1);
+        assert_eq!(stats.retries as usize, retries);
+        assert_eq!(stats.gave_up, 1);
+    }
+
+    #[test]
+    fn inflight_statuses_parse() {
+        let parse = |body: &str| get_inflight_status(&serde_json::from_str(body).unwrap());
+        assert_eq!(parse(PENDING), Some((InflightStatus::Pending, None)));
+        assert_eq!(
+            parse(LANDED),
+            Some((InflightStatus::Landed, Some(280999029)))
+        );
+        assert_eq!(parse(LANDED_NO_SLOT), Some((InflightStatus::Landed, None)));
+        assert_eq!(parse(FAILED), Some((InflightStatus::Failed, None)));
+        assert_eq!(parse(INVALID), Some((InflightStatus::Invalid, None)));
+        assert_eq!(parse(&PENDING.replace("Pending", "Unknown")), None);
+        assert_eq!(
+            parse(r#"{"jsonrpc":"2.0","result":{"context":{"slot":1},"value":[]},"id":1}"#),
+            None
+        );
+        assert_eq!(
+            parse(r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"bad"},"id":1}"#),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn terminal_statuses_end_the_wait() {
+        let (outcome, polls) = wait_on(&[LANDED], Duration::from_secs(5)).await;
+        assert_eq!(
+            outcome,
+            BundleOutcome::Landed {
+                slot: Some(280999029)
+            }
+        );
+        assert_eq!(polls, 1);
+        // no made up slot when the engine leaves it out
+        let (outcome, _) = wait_on(&[LANDED_NO_SLOT], Duration::from_secs(5)).await;
+        assert_eq!(outcome, BundleOutcome::Landed { slot: None });
+        let (outcome, polls) = wait_on(&[FAILED], Duration::from_secs(5)).await;
+        assert_eq!(outcome, BundleOutcome::Failed);
+        assert_eq!(polls, 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_after_pending_is_dropped() {
+        let (outcome, polls) = wait_on(&[PENDING, INVALID], Duration::from_secs(5)).await;
+        assert_eq!(outcome, BundleOutcome::Dropped);
+        assert_eq!(polls, 2);
+        assert!(outcome.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn one_invalid_is_not_trusted() {
+        // a fresh bundle may not be indexed yet
+        let (outcome, polls) = wait_on(&[INVALID, LANDED], Duration::from_secs(5)).await;
+        assert_eq!(
+            outcome,
+            BundleOutcome::Landed {
+                slot: Some(280999029)
+            }
+        );
+        assert_eq!(polls, 2);
+        let (outcome, polls) = wait_on(&[INVALID, INVALID], Duration::from_secs(5)).await;
+        assert_eq!(outcome, BundleOutcome::Invalid);
+        assert_eq!(polls, 2);
+    }
+
+    #[tokio::test]
+    async fn pending_until_the_wait_runs_out() {
+        let (outcome, _) = wait_on(&[PENDING], Duration::from_millis(300)).await;
+        assert_eq!(outcome, BundleOutcome::Timeout);
+        assert!(!outcome.is_retryable());
+    }
+
+    #[test]
+    fn rate_limit_keys() {
+        let ny = JitoClient::new("https://ny.example/api/v1", Some("uuid-1".to_string()));
+        let tokyo = JitoClient::new("https://tokyo.example/api/v1", Some("uuid-1".to_string()));
+        let other_uuid = JitoClient::new("https://ny.example/api/v1", Some("uuid-2".to_string()));
+        let no_uuid = JitoClient::new("https://ny.example/api/v1", None);
+
+        let send = ny.rate_limit_key(JitoRequest::Send);
+        assert_ne!(send, tokyo.rate_limit_key(JitoRequest::Send));
+        assert_ne!(send, other_uuid.rate_limit_key(JitoRequest::Send));
+        assert_ne!(send, ny.rate_limit_key(JitoRequest::Status));
+        // tip accounts are per engine whatever the uuid
+        assert_eq!(
+            ny.rate_limit_key(JitoRequest::TipAccounts),
+            no_uuid.rate_limit_key(JitoRequest::TipAccounts)
+        );
+        assert_ne!(
+            ny.rate_limit_key(JitoRequest::TipAccounts),
+            tokyo.rate_limit_key(JitoRequest::TipAccounts)
+        );
+        for request in [
+            JitoRequest::Send,
+            JitoRequest::Status,
+            JitoRequest::TipAccounts,
+        ] {
+            assert!(!no_uuid.rate_limit_key(request).is_empty());
+        }
+    }
 }
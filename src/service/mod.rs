@@ -1,2 +1,5 @@
+pub mod block_engine;
 pub mod jito;
 pub mod nextblock;
+pub mod rate_limit;
+pub mod tip_stream;
@@ -0,0 +1,177 @@
+use std::{str::FromStr, sync::atomic::AtomicUsize, time::Duration};
+
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use log::{error, info};
+use serde_json::{Value, json};
+use solana_program::system_instruction;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::VersionedTransaction};
+use spl_token::ui_amount_to_amount;
+use tokio::time::Instant;
+
+use crate::utils::jjj::{import_env_var, import_env_var_with_default, round_robin};
+
+// https://docs.nextblock.io, one of these has to receive the tip
+pub const NEXTBLOCK_TIP_ACCOUNTS: [&str; 8] = [
+    "NEXTbLoCkB51HpLBLojQfpyVAMorm3zzKg7w9NFdqid",
+    "NeXTBLoCKs9F1y5PJS9CKrFNNLU1keHW71rfh7KgA1X",
+    "NexTBLockJYZ7QD7p2byrUa6df8ndV2WSd8GkbWqfbb",
+    "neXtBLock1LeC67jYd1QdAa32kbVeubsfPNTJC1V5At",
+    "nEXTBLockYgngeRmRrjDV31mGSekVPqZoMGhQEZtPVG",
+    "nextBLoCkPMgmG8ZgJtABeScP35qLa2AMCNKntAP7Xc",
+    "NextbLoCkVtMGcV47JzewQdvBpLqT9TxQFozQkN98pE",
+    "NexTbLoCkWykbLuB1NkjXgFWkX9oAtcoagQegygXXA2",
+];
+
+/// Outcome of one submission, kept for reporting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NextBlockSubmitResult {
+    pub signature: String,
+    pub elapsed: Duration,
+}
+
+pub struct NextBlockClient {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+    tip_accounts: Vec<Pubkey>,
+}
+
+impl NextBlockClient {
+    pub fn new(url: &str, api_key: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            tip_accounts: NEXTBLOCK_TIP_ACCOUNTS
+                .iter()
+                .map(|account| Pubkey::from_str(account).unwrap())
+                .collect(),
+        }
+    }
+
+    /// NEXTBLOCK_URL and NEXTBLOCK_API_KEY
+    pub fn from_env() -> Self {
+        let url: String =
+            import_env_var_with_default("NEXTBLOCK_URL", "https://ny.nextblock.io".to_string());
+        Self::new(&url, &import_env_var("NEXTBLOCK_API_KEY"))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn pick_tip_account(&self) -> Pubkey {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        round_robin(&NEXT, &self.tip_accounts).unwrap()
+    }
+
+    /// transfer of the current tip value to one of the tip accounts
+    pub fn tip_instruction(&self, payer: &Pubkey) -> Instruction {
+        let tip_account = self.pick_tip_account();
+        let tip_lamports = nextblock_tip_lamports();
+        info!(
+            "nextblock tip account: {}, lamports: {}",
+            tip_account, tip_lamports
+        );
+        system_instruction::transfer(payer, &tip_account, tip_lamports)
+    }
+
+    /// submit a signed transaction that already carries the tip
+    pub async fn submit(&self, txn: &VersionedTransaction) -> Result<NextBlockSubmitResult> {
+        let start_time = Instant::now();
+        let body = json!({
+            "transaction": {"content": STANDARD.encode(bincode::serialize(txn)?)},
+            "frontRunningProtection": import_env_var_with_default("NEXTBLOCK_FRONT_RUNNING_PROTECTION", false),
+        });
+        let response = self
+            .http
+            .post(format!("{}/api/v2/submit", self.url))
+            .header("Authorization", &self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        // error pages from a proxy in front aren't json, keep them readable
+        let text = response.text().await?;
+        if !status.is_success() {
+            error!("nextblock submit failed: {}, {}", status, text);
+            return Err(anyhow!("nextblock submit failed: {}, {}", status, text));
+        }
+        let response: Value = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("nextblock submit returned invalid json: {}, {}", e, text))?;
+        let signature = response["signature"]
+            .as_str()
+            .ok_or_else(|| anyhow!("nextblock submit returned no signature: {}", response))?
+            .to_string();
+        let result = NextBlockSubmitResult {
+            signature,
+            elapsed: start_time.elapsed(),
+        };
+        info!(
+            "nextblock signature: {}, elapsed: {:?}",
+            result.signature, result.elapsed
+        );
+        Ok(result)
+    }
+}
+
+/// NEXTBLOCK_TIP_VALUE in lamports, nextblock rejects tips below 0.001 SOL
+pub fn nextblock_tip_lamports() -> u64 {
+    ui_amount_to_amount(
+        import_env_var_with_default("NEXTBLOCK_TIP_VALUE", 0.001),
+        spl_token::native_mint::DECIMALS,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::http_stub::HttpStub;
+
+    #[tokio::test]
+    async fn submit_posts_transaction_with_api_key() {
+        let stub = HttpStub::start(200, r#"{"signature":"5sig"}"#, Duration::ZERO).await;
+        let client = NextBlockClient::new(&format!("{}/", stub.url), "secret-key");
+        let txn = VersionedTransaction::default();
+
+        let result = client.submit(&txn).await.unwrap();
+        assert_eq!(result.signature, "5sig");
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/v2/submit");
+        assert_eq!(request.headers["authorization"], "secret-key");
+        let body = request.json();
+        assert_eq!(
+            body["transaction"]["content"],
+            STANDARD.encode(bincode::serialize(&txn).unwrap())
+        );
+        assert_eq!(body["frontRunningProtection"], false);
+    }
+
+    #[tokio::test]
+    async fn submit_maps_errors() {
+        let txn = VersionedTransaction::default();
+        for (status, body, expected) in [
+            (401, r#"{"error":"invalid api key"}"#, "401"),
+            (502, "<html>bad gateway</html>", "bad gateway"),
+            (200, r#"{"result":"ok"}"#, "no signature"),
+            (200, "not json", "invalid json"),
+        ] {
+            let stub = HttpStub::start(status, body, Duration::ZERO).await;
+            let err = NextBlockClient::new(&stub.url, "key")
+                .submit(&txn)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", status, err);
+        }
+    }
+
+    #[test]
+    fn tip_in_lamports() {
+        assert_eq!(nextblock_tip_lamports(), 1_000_000);
+    }
+}
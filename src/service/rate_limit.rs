@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use log::info;
+use tokio::time::{Instant, sleep};
+
+use crate::utils::jjj::import_env_var_with_default;
+
+static JITO_LIMITER: LazyLock<RateLimiter> = LazyLock::new(|| {
+    let rate = import_env_var_with_default("JITO_RATE_LIMIT", 1.0);
+    RateLimiter::new(
+        rate,
+        import_env_var_with_default("JITO_RATE_BURST", rate.max(1.0)),
+    )
+});
+
+/// Tokens refill at `rate` per second up to `burst`. Taking a token when
+/// none is left reserves the next one, so callers queue in arrival order.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            updated_at: Instant::now(),
+        }
+    }
+
+    /// take a token, returns how long to wait before using it
+    pub fn take(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated_at = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 || self.rate <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Throttling of one key since start.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThrottleStats {
+    pub requests: u64,
+    /// requests held back by the local limiter
+    pub delayed: u64,
+    pub delayed_for: Duration,
+    /// 429 answers from the remote side
+    pub rate_limited: u64,
+    pub retries: u64,
+    /// requests still rate limited after the last retry
+    pub gave_up: u64,
+}
+
+/// One token bucket per key, e.g. per jito engine and uuid.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+    stats: Mutex<HashMap<String, ThrottleStats>>,
+}
+
+impl RateLimiter {
+    /// `rate` requests per second, 0 disables the limit
+    pub fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            buckets: Mutex::new(HashMap::new()),
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// wait for the next free slot of `key`
+    pub async fn acquire(&self, key: &str) {
+        let wait = if self.rate > 0.0 {
+            self.buckets
+                .lock()
+                .unwrap()
+                .entry(key.to_string())
+                .or_insert_with(|| TokenBucket::new(self.rate, self.burst))
+                .take()
+        } else {
+            Duration::ZERO
+        };
+        self.update(key, |stats| {
+            stats.requests += 1;
+            if !wait.is_zero() {
+                stats.delayed += 1;
+                stats.delayed_for += wait;
+            }
+        });
+        if !wait.is_zero() {
+            info!("rate limit {:?}, waiting {:?}", key, wait);
+            sleep(wait).await;
+        }
+    }
+
+    pub fn update(&self, key: &str, f: impl FnOnce(&mut ThrottleStats)) {
+        f(self
+            .stats
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default());
+    }
+
+    pub fn stats(&self) -> HashMap<String, ThrottleStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+/// exponential backoff for retry `attempt` (from 0) plus up to `base` of jitter
+pub fn backoff_with_jitter(base: Duration, attempt: u32) -> Duration {
+    // no rng dependency, the clock's sub-second part is random enough here
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    let jitter = Duration::from_nanos(nanos % (base.as_nanos() as u64).max(1));
+    base * 2u32.saturating_pow(attempt.min(16)) + jitter
+}
+
+/// process wide jito limiter, JITO_RATE_LIMIT requests per second with bursts
+/// of JITO_RATE_BURST for every engine and uuid, status polls separately
+/// from sends. a bundle fanned out to several regions takes one token from
+/// each region's bucket.
+pub fn get_jito_limiter() -> &'static RateLimiter {
+    &JITO_LIMITER
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::advance;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_bursts_then_refills() {
+        let mut bucket = TokenBucket::new(4.0, 3.0);
+        for _ in 0..3 {
+            assert_eq!(bucket.take(), Duration::ZERO);
+        }
+        // past the burst each token is reserved a quarter second further out
+        assert_eq!(bucket.take(), Duration::from_millis(250));
+        assert_eq!(bucket.take(), Duration::from_millis(500));
+
+        // three tokens refill the debt of two, one is free
+        advance(Duration::from_millis(750)).await;
+        assert_eq!(bucket.take(), Duration::ZERO);
+        assert_eq!(bucket.take(), Duration::from_millis(250));
+
+        // an idle bucket fills up to the burst, not beyond
+        advance(Duration::from_secs(60)).await;
+        for _ in 0..3 {
+            assert_eq!(bucket.take(), Duration::ZERO);
+        }
+        assert_eq!(bucket.take(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_keys_are_isolated() {
+        let limiter = RateLimiter::new(1.0, 1.0);
+        let start = Instant::now();
+        limiter.acquire("ny").await;
+        limiter.acquire("tokyo").await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        limiter.acquire("ny").await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        let stats = limiter.stats();
+        assert_eq!(stats["ny"].requests, 2);
+        assert_eq!(stats["ny"].delayed, 1);
+        assert_eq!(stats["ny"].delayed_for, Duration::from_secs(1));
+        assert_eq!(stats["tokyo"].requests, 1);
+        assert_eq!(stats["tokyo"].delayed, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_rate_never_waits() {
+        let limiter = RateLimiter::new(0.0, 1.0);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire("ny").await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.stats()["ny"].delayed, 0);
+    }
+
+    #[test]
+    fn jitter_stays_below_the_base() {
+        let base = Duration::from_millis(200);
+        for attempt in 0..5 {
+            let backoff = base * 2u32.pow(attempt);
+            for _ in 0..100 {
+                let wait = backoff_with_jitter(base, attempt);
+                assert!(wait >= backoff && wait < backoff + base, "{:?}", wait);
+            }
+        }
+        // the exponent stops growing at 2^16
+        assert!(backoff_with_jitter(base, 100) < base * 65537);
+        assert_eq!(backoff_with_jitter(Duration::ZERO, 3), Duration::ZERO);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures_util::StreamExt;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::time::{Instant, sleep};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::utils::jjj::{import_env_var_with_default, import_env_var_with_option};
+
+static TIP_FLOOR: LazyLock<Option<Arc<TipFloorSubscriber>>> = LazyLock::new(|| {
+    let url = import_env_var_with_option("JITO_TIP_STREAM_URL")?
+        .into_string()
+        .ok()?;
+    // the subscriber needs a runtime, callers outside of one get the fixed tip
+    tokio::runtime::Handle::try_current().ok()?;
+    Some(TipFloorSubscriber::spawn(url))
+});
+
+/// Landed tip percentiles in SOL, one message of the jito tip stream.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct TipFloor {
+    #[serde(rename = "landed_tips_25th_percentile")]
+    pub p25: f64,
+    #[serde(rename = "landed_tips_50th_percentile")]
+    pub p50: f64,
+    #[serde(rename = "landed_tips_75th_percentile")]
+    pub p75: f64,
+    #[serde(rename = "landed_tips_95th_percentile")]
+    pub p95: f64,
+    #[serde(rename = "landed_tips_99th_percentile")]
+    pub p99: f64,
+}
+
+impl TipFloor {
+    /// the published percentile closest to `percentile`, rounding up
+    pub fn percentile(&self, percentile: u8) -> f64 {
+        match percentile {
+            0..=25 => self.p25,
+            26..=50 => self.p50,
+            51..=75 => self.p75,
+            76..=95 => self.p95,
+            _ => self.p99,
+        }
+    }
+}
+
+/// the stream sends a one element array, a bare object is accepted as well
+pub fn parse_tip_floor(text: &str) -> Result<TipFloor> {
+    let value: Value = serde_json::from_str(text)?;
+    let value = match value {
+        Value::Array(mut values) if !values.is_empty() => values.swap_remove(0),
+        Value::Object(_) => value,
+        _ => return Err(anyhow!("unexpected tip stream message: {}", text)),
+    };
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Background task following a jito tip stream, keeps the latest tip floor.
+pub struct TipFloorSubscriber {
+    url: String,
+    latest: RwLock<Option<(Instant, TipFloor)>>,
+}
+
+impl TipFloorSubscriber {
+    /// connect to `url` and keep reconnecting until the process exits
+    pub fn spawn(url: String) -> Arc<Self> {
+        let subscriber = Arc::new(Self {
+            url,
+            latest: RwLock::new(None),
+        });
+        let task = subscriber.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = task.run().await {
+                    warn!("tip stream {} disconnected: {}", task.url, err);
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        });
+        subscriber
+    }
+
+    async fn run(&self) -> Result<()> {
+        let (mut stream, _) = connect_async(self.url.as_str()).await?;
+        info!("tip stream connected: {}", self.url);
+        while let Some(message) = stream.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            match parse_tip_floor(&text) {
+                Ok(floor) => *self.latest.write().unwrap() = Some((Instant::now(), floor)),
+                Err(err) => warn!("tip stream message skipped: {}", err),
+            }
+        }
+        Err(anyhow!("stream closed"))
+    }
+
+    /// latest tip floor, None when nothing arrived within `max_age`
+    pub fn latest(&self, max_age: Duration) -> Option<TipFloor> {
+        match *self.latest.read().unwrap() {
+            Some((received_at, floor)) if received_at.elapsed() <= max_age => Some(floor),
+            _ => None,
+        }
+    }
+}
+
+/// process wide subscriber, started on first use when JITO_TIP_STREAM_URL is
+/// set. call it once at startup so the floor is warm before the first trade.
+pub fn get_tip_floor_subscriber() -> Option<Arc<TipFloorSubscriber>> {
+    TIP_FLOOR.clone()
+}
+
+/// tip in SOL at JITO_TIP_PERCENTILE of the live floor, clamped with
+/// `clamp_tip`. None when there is no fresh floor.
+pub fn get_tip_from_floor() -> Option<f64> {
+    let max_age = Duration::from_secs(import_env_var_with_default(
+        "JITO_TIP_STREAM_MAX_AGE_SECS",
+        60,
+    ));
+    let floor = get_tip_floor_subscriber()?.latest(max_age)?;
+    let percentile: u8 = import_env_var_with_default("JITO_TIP_PERCENTILE", 50);
+    Some(clamp_tip(floor.percentile(percentile)))
+}
+
+/// `tip` in SOL limited to JITO_TIP_MIN..=JITO_TIP_MAX
+pub fn clamp_tip(tip: f64) -> f64 {
+    let min: f64 = import_env_var_with_default("JITO_TIP_MIN", 0.00001);
+    let max: f64 = import_env_var_with_default("JITO_TIP_MAX", 0.01);
+    clamp_tip_to(tip, min, max)
+}
+
+fn clamp_tip_to(tip: f64, min: f64, max: f64) -> f64 {
+    tip.clamp(min, max.max(min))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::SinkExt;
+    use tokio::{net::TcpListener, time::timeout};
+    use tokio_tungstenite::accept_async;
+
+    use super::*;
+
+    const MESSAGE: &str = r#"[{"time":"2024-01-01T00:00:00Z","landed_tips_25th_percentile":0.000001,"landed_tips_50th_percentile":0.00002,"landed_tips_75th_percentile":0.0001,"landed_tips_95th_percentile":0.001,"landed_tips_99th_percentile":0.05,"ema_landed_tips_50th_percentile":0.00002}]"#;
+
+    // stands in for the jito tip stream: one floor message, then stays open
+    async fn serve_tip_stream() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(Message::text(MESSAGE)).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+        format!("ws://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn subscriber_follows_stream() {
+        let subscriber = TipFloorSubscriber::spawn(serve_tip_stream().await);
+        let floor = timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(floor) = subscriber.latest(Duration::from_secs(60)) {
+                    return floor;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(floor.percentile(50), 0.00002);
+        assert_eq!(floor.percentile(60), 0.0001);
+        assert_eq!(floor.percentile(99), 0.05);
+        // the 99th is above the max, the 25th below the min
+        assert_eq!(clamp_tip_to(floor.percentile(99), 0.00001, 0.01), 0.01);
+        assert_eq!(clamp_tip_to(floor.percentile(25), 0.00001, 0.01), 0.00001);
+        assert_eq!(clamp_tip_to(floor.percentile(75), 0.00001, 0.01), 0.0001);
+        // a max below the min is lifted to the min
+        assert_eq!(clamp_tip_to(0.5, 0.001, 0.0), 0.001);
+    }
+
+    #[test]
+    fn parses_bare_object() {
+        let object = &MESSAGE[1..MESSAGE.len() - 1];
+        assert_eq!(parse_tip_floor(object).unwrap().p95, 0.001);
+        assert!(parse_tip_floor("[]").is_err());
+    }
+}
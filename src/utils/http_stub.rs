@@ -0,0 +1,116 @@
+//! A minimal http/1.1 server standing in for remote apis in tests. Requests
+//! get canned answers, the requests are kept for asserts.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    time::sleep,
+};
+
+#[derive(Clone, Debug)]
+pub struct StubRequest {
+    pub method: String,
+    /// path and query, e.g. /api/v1/bundles?uuid=x
+    pub path: String,
+    /// lowercase header names
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl StubRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+}
+
+pub struct HttpStub {
+    pub url: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+}
+
+impl HttpStub {
+    /// answer every request with `status` and the json `body` after `delay`
+    pub async fn start(status: u16, body: &str, delay: Duration) -> Self {
+        Self::start_sequence(status, &[body], delay).await
+    }
+
+    /// answer the requests with `bodies` in turn, the last one repeats
+    pub async fn start_sequence(status: u16, bodies: &[&str], delay: Duration) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = requests.clone();
+        let responses: Arc<Vec<String>> = Arc::new(
+            bodies
+                .iter()
+                .map(|body| {
+                    format!(
+                        "HTTP/1.1 {} Stub\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                })
+                .collect(),
+        );
+        let next = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            loop {
+                let Ok((tcp, _)) = listener.accept().await else {
+                    return;
+                };
+                let (seen, responses, next) = (seen.clone(), responses.clone(), next.clone());
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(tcp);
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    let mut parts = line.split_whitespace();
+                    let (method, path) = (
+                        parts.next().unwrap_or_default().to_string(),
+                        parts.next().unwrap_or_default().to_string(),
+                    );
+                    let mut headers = HashMap::new();
+                    loop {
+                        line.clear();
+                        reader.read_line(&mut line).await.unwrap();
+                        let Some((name, value)) = line.trim_end().split_once(':') else {
+                            break;
+                        };
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                    }
+                    let length = headers
+                        .get("content-length")
+                        .and_then(|length| length.parse().ok())
+                        .unwrap_or(0);
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).await.unwrap();
+                    seen.lock().unwrap().push(StubRequest {
+                        method,
+                        path,
+                        headers,
+                        body: String::from_utf8(body).unwrap(),
+                    });
+                    let index = next.fetch_add(1, Ordering::SeqCst).min(responses.len() - 1);
+                    sleep(delay).await;
+                    let mut tcp = reader.into_inner();
+                    let _ = tcp.write_all(responses[index].as_bytes()).await;
+                    let _ = tcp.shutdown().await;
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
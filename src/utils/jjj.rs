@@ -5,6 +5,7 @@ use std::env;
 use std::ffi::{OsStr, OsString};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub fn import_env_var(key: &str) -> String {
     env::var(key).unwrap_or_else(|_| panic!("Environment variable {} is not set", key))
@@ -46,3 +47,13 @@ pub async fn create_nonblocking_rpc_client()
     );
     Ok(Arc::new(rpc_client))
 }
+
+/// next item of `items` in turn, `next` being the caller's own counter.
+/// used for fee and tip accounts, so consecutive transactions don't all
+/// write-lock the same account.
+pub fn round_robin<T: Copy>(next: &AtomicUsize, items: &[T]) -> Option<T> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(items[next.fetch_add(1, Ordering::Relaxed) % items.len()])
+}
@@ -1 +1,4 @@
-pub mod jjj;
\ No newline at end of file
+pub mod jjj;
+
+#[cfg(test)]
+pub mod http_stub;